@@ -1,4 +1,6 @@
-use clap::{Arg, Command, ValueHint};
+use crate::daemon::unix_socket::command::ConfigKey;
+
+use clap::{builder::PossibleValuesParser, Arg, ArgAction, Command, ValueHint};
 
 pub fn build<'a>() -> Command {
     Command::new("earbuds")
@@ -9,6 +11,7 @@ pub fn build<'a>() -> Command {
         //.help("Control your Galaxy Buds live from cli")
         .arg(
             Arg::new("verbose")
+                .action(ArgAction::SetTrue)
                 .short('v')
                 .long("verbose")
                 .global(true)
@@ -29,23 +32,44 @@ pub fn build<'a>() -> Command {
         )
         .arg(
             Arg::new("daemon")
+                .action(ArgAction::SetTrue)
                 .help("Starts the daemon")
                 .long("daemon")
                 .short('d'),
         )
         .arg(
             Arg::new("no-fork")
+                .action(ArgAction::SetTrue)
                 .help("Don't fork the daemon")
                 .long("no-fork"),
         )
+        .arg(
+            Arg::new("fake-buds")
+                .help("Simulate buds using a script instead of connecting to real ones")
+                .long("fake-buds")
+                .hide(true)
+                .num_args(1)
+                .requires("no-fork")
+                .value_hint(ValueHint::FilePath),
+        )
+        .arg(
+            Arg::new("record")
+                .help("Record the traffic of all buds into a capture file")
+                .long("record")
+                .num_args(1)
+                .requires("no-fork")
+                .value_hint(ValueHint::FilePath),
+        )
         .arg(
             Arg::new("kill-daemon")
+                .action(ArgAction::SetTrue)
                 .help("Kill the daemon. If used together with -d, the daemon will get restarted")
                 .short('k')
                 .long("kill-daemon"),
         )
         .arg(
             Arg::new("quiet")
+                .action(ArgAction::SetTrue)
                 .help("Don't print extra output")
                 .short('q')
                 .global(true)
@@ -54,7 +78,9 @@ pub fn build<'a>() -> Command {
         .arg(
             Arg::new("device")
                 .global(true)
-                .help("Specify the device to use")
+                .help(
+                    "Specify the device to use (address, alias, bluetooth name or address prefix)",
+                )
                 .short('s')
                 .num_args(1)
                 .value_hint(ValueHint::Unknown)
@@ -65,6 +91,10 @@ pub fn build<'a>() -> Command {
                 .alias("info")
                 .about("Display informations for a given device"),
         )
+        .subcommand(
+            Command::new("watch")
+                .about("Continuously display informations and update them on changes"),
+        )
         .subcommand(
             Command::new("set")
                 .about("Turn on/off features and control the equalizer setting")
@@ -111,6 +141,87 @@ pub fn build<'a>() -> Command {
                     "touchpad",
                 ])),
         )
+        .subcommand(
+            Command::new("apply")
+                .arg_required_else_help(true)
+                .about("Apply multiple settings at once from a profile file")
+                .arg(
+                    Arg::new("file")
+                        .required(true)
+                        .num_args(1)
+                        .value_hint(ValueHint::FilePath),
+                ),
+        )
+        .subcommand(
+            Command::new("battery")
+                .arg_required_else_help(true)
+                .about("Show battery informations")
+                .subcommand(
+                    Command::new("history")
+                        .about("Show the recorded battery levels")
+                        .arg(
+                            Arg::new("since").long("since").num_args(1).help(
+                                "Only show levels since a given time (e.g. 2h, 3d, 2024-01-31)",
+                            ),
+                        )
+                        .arg(
+                            Arg::new("csv")
+                                .long("csv")
+                                .action(ArgAction::SetTrue)
+                                .help("Print the levels as csv"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("replay")
+                .arg_required_else_help(true)
+                .about("Run the daemon in the foreground using a capture instead of real buds")
+                .arg(
+                    Arg::new("file")
+                        .required(true)
+                        .num_args(1)
+                        .value_hint(ValueHint::FilePath),
+                ),
+        )
+        .subcommand(
+            Command::new("profile")
+                .arg_required_else_help(true)
+                .about("Manage named profiles of buds settings")
+                .subcommand(Command::new("list").about("List all stored profiles"))
+                .subcommand(
+                    Command::new("save")
+                        .arg_required_else_help(true)
+                        .about("Store the current settings as profile")
+                        .arg(Arg::new("name").required(true).num_args(1)),
+                )
+                .subcommand(
+                    Command::new("load")
+                        .arg_required_else_help(true)
+                        .about("Apply the settings of a profile")
+                        .arg(Arg::new("name").required(true).num_args(1)),
+                )
+                .subcommand(
+                    Command::new("delete")
+                        .arg_required_else_help(true)
+                        .about("Delete a profile")
+                        .arg(Arg::new("name").required(true).num_args(1)),
+                ),
+        )
+        .subcommand(
+            Command::new("devices")
+                .about("List all known devices")
+                .subcommand(
+                    Command::new("set-default")
+                        .arg_required_else_help(true)
+                        .about("Make a device the default device")
+                        .arg(
+                            Arg::new("name")
+                                .required(true)
+                                .num_args(1)
+                                .help("Address, alias, bluetooth name or address prefix"),
+                        ),
+                ),
+        )
         .subcommand(
             Command::new("config")
                 .arg_required_else_help(true)
@@ -119,17 +230,40 @@ pub fn build<'a>() -> Command {
                     Command::new("set")
                         .arg_required_else_help(true)
                         .about("Set a config value")
-                        .arg(Arg::new("key").required(true).num_args(1).value_parser([
-                            "auto-pause",
-                            "auto-play",
-                            "low-battery-notification",
-                            "smart-sink",
-                        ]))
-                        .arg(Arg::new("value").required(true).num_args(1)),
-                ),
+                        .arg(config_key_arg())
+                        .arg(
+                            Arg::new("value")
+                                .required(true)
+                                .num_args(1)
+                                .help("Lists of thresholds are separated by commas (e.g. 30,15,5)"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("get")
+                        .arg_required_else_help(true)
+                        .about("Get a config value")
+                        .arg(config_key_arg()),
+                )
+                .subcommand(
+                    Command::new("unset")
+                        .arg_required_else_help(true)
+                        .about("Remove a config value to use its default")
+                        .arg(config_key_arg()),
+                )
+                .subcommand(Command::new("list").about("List all config values")),
         )
         // Connect
         .subcommand(Command::new("connect").about("Connect your earbuds"))
         // Disconnect
         .subcommand(Command::new("disconnect").about("Disconnect your earbuds"))
 }
+
+// The key of a config option
+fn config_key_arg() -> Arg {
+    Arg::new("key")
+        .required(true)
+        .num_args(1)
+        .value_parser(PossibleValuesParser::new(
+            ConfigKey::ALL.iter().map(|i| i.name()),
+        ))
+}
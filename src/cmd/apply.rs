@@ -0,0 +1,89 @@
+use super::socket_client::{self, SocketClient};
+use super::utils;
+use crate::daemon::buds_profile::BudsProfile;
+use crate::daemon::unix_socket::command::{SettingResult, SettingStatus};
+
+use clap::ArgMatches;
+
+use std::fs;
+use std::process::exit;
+
+/// Apply all settings of a profile file
+pub fn apply(sc: &mut SocketClient, app: &ArgMatches) {
+    let file = app.get_one::<String>("file").unwrap();
+
+    let profile = match fs::read_to_string(file)
+        .map_err(|e| e.to_string())
+        .and_then(|content| toml::from_str::<BudsProfile>(&content).map_err(|e| e.to_string()))
+    {
+        Ok(profile) => profile,
+        Err(err) => {
+            eprintln!("Can't read profile {}: {}", file, err);
+            exit(1);
+        }
+    };
+
+    let settings = match profile.to_settings() {
+        Ok(settings) => settings,
+        Err(err) => {
+            eprintln!("Invalid profile: {}", err);
+            exit(1);
+        }
+    };
+
+    if settings.is_empty() {
+        println!("Nothing to apply");
+        return;
+    }
+
+    // Do unix_socket request
+    let request =
+        socket_client::new_apply_settings_request(utils::get_device_from_app(app), settings);
+    let res = match sc.do_request(request) {
+        Ok(k) => k,
+        Err(err) => {
+            eprintln!("{:?}", err);
+            exit(1);
+        }
+    };
+
+    // print as json if user desires so
+    if utils::print_as_json(app) {
+        println!("{}", res);
+        if !socket_client::to_response::<Vec<SettingResult>>(&res).is_success() {
+            exit(1);
+        }
+        return;
+    }
+
+    print_results(&res);
+}
+
+/// Print the result of every applied setting in a human readable
+/// way. Exits with an error if not all settings got applied
+pub fn print_results(res: &str) {
+    let res = socket_client::to_response::<Vec<SettingResult>>(res);
+    for result in res.payload.iter().flatten() {
+        match result.status {
+            SettingStatus::Applied => println!("{}\tApplied", result.setting),
+            SettingStatus::Skipped => println!("{}\tSkipped", result.setting),
+            SettingStatus::Failed => println!(
+                "{}\tFailed: {}",
+                result.setting,
+                result.error.clone().unwrap_or_default()
+            ),
+        }
+    }
+
+    if res.is_success() {
+        println!("Success");
+        return;
+    }
+
+    if let Some(err_msg) = res.status_message {
+        println!("Error: {}", err_msg);
+    } else {
+        println!("Error!")
+    }
+    exit(1);
+}
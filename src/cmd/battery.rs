@@ -0,0 +1,259 @@
+use super::utils;
+use crate::daemon::battery_history::{self, BatterySample};
+use crate::daemon::buds_config::Config;
+
+use clap::ArgMatches;
+
+use std::convert::TryFrom;
+
+/// Show the recorded battery levels
+pub fn history(app: &ArgMatches) {
+    let since = match app.get_one::<String>("since") {
+        Some(since) => match parse_since(since) {
+            Some(since) => Some(since),
+            None => {
+                println!("Invalid time: '{}'. Use e.g. 2h, 3d or 2024-01-31", since);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    // Allow selecting the device by its alias, name or address prefix as well
+    let device = match utils::get_device_from_app(app) {
+        Some(query) => match resolve_device(&query) {
+            Ok(address) => Some(address),
+            Err(err) => {
+                println!("{}", err);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let samples = match battery_history::load(device.as_deref(), since) {
+        Ok(samples) => samples,
+        Err(err) => {
+            eprintln!("Can't read battery history: {}", err);
+            return;
+        }
+    };
+
+    if utils::print_as_json(app) {
+        println!("{}", serde_json::to_string(&samples).unwrap());
+    } else if app.get_flag("csv") {
+        print_csv(&samples);
+    } else {
+        print_table(&samples);
+    }
+}
+
+// Get the address of a device by its address, alias, name or address prefix
+fn resolve_device(query: &str) -> Result<String, String> {
+    async_std::task::block_on(Config::read())?.resolve_device(query, &[])
+}
+
+fn print_table(samples: &[BatterySample]) {
+    if samples.is_empty() {
+        println!("No battery history recorded");
+        return;
+    }
+
+    println!(
+        "{:<20}  {:<17}  {:>4}  {:>5}  {:>4}  Placement (L/R)",
+        "Time (UTC)", "Device", "Left", "Right", "Case"
+    );
+    for sample in samples {
+        println!(
+            "{:<20}  {:<17}  {:>3}%  {:>4}%  {:>3}%  {:?}/{:?}",
+            format_timestamp(sample.timestamp),
+            sample.address,
+            sample.left,
+            sample.right,
+            sample.case,
+            sample.placement_left,
+            sample.placement_right
+        );
+    }
+}
+
+fn print_csv(samples: &[BatterySample]) {
+    println!("timestamp,address,left,right,case,placement_left,placement_right");
+    for sample in samples {
+        println!(
+            "{},{},{},{},{},{:?},{:?}",
+            sample.timestamp,
+            sample.address,
+            sample.left,
+            sample.right,
+            sample.case,
+            sample.placement_left,
+            sample.placement_right
+        );
+    }
+}
+
+/// Parse a relative time (30m, 2h, 3d, 1w), a date (YYYY-MM-DD) or
+/// a unix timestamp into a unix timestamp
+fn parse_since(s: &str) -> Option<u64> {
+    if let Ok(timestamp) = s.parse::<u64>() {
+        return Some(timestamp);
+    }
+
+    // Date in the format YYYY-MM-DD
+    let date: Vec<&str> = s.split('-').collect();
+    if let [year, month, day] = date[..] {
+        let (year, month, day) = (year.parse().ok()?, month.parse().ok()?, day.parse().ok()?);
+        if !(1970..=9999).contains(&year)
+            || !(1..=12).contains(&month)
+            || !(1..=days_in_month(year, month)).contains(&day)
+        {
+            return None;
+        }
+
+        return u64::try_from(days_from_civil(year, month, day) * 86400).ok();
+    }
+
+    // Relative time
+    let unit = match s.chars().last()? {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    let amount = s[..s.len() - 1].parse::<u64>().ok()?;
+
+    Some(battery_history::now().saturating_sub(amount.checked_mul(unit)?))
+}
+
+// Count of days of a month
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Format a unix timestamp as UTC date and time
+fn format_timestamp(timestamp: u64) -> String {
+    let (year, month, day) = civil_from_days((timestamp / 86400) as i64);
+    let secs = timestamp % 86400;
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+// Days since the unix epoch of a date
+// (see http://howardhinnant.github.io/date_algorithms.html)
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let yoe = year - era * 400;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+// Date of a count of days since the unix epoch
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = if days >= 0 { days } else { days - 146096 } / 146097;
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_dates() {
+        assert_eq!(parse_since("1970-01-01"), Some(0));
+        assert_eq!(parse_since("2024-01-31"), Some(1706659200));
+        assert_eq!(parse_since("2024-2-29"), Some(1709164800));
+        assert_eq!(parse_since("2000-02-29"), Some(951782400));
+        assert_eq!(parse_since("2024-12-31"), Some(1735603200));
+    }
+
+    #[test]
+    fn parse_invalid_dates() {
+        for date in [
+            "2023-02-29",
+            "1900-02-29",
+            "2024-04-31",
+            "2024-13-01",
+            "2024-00-10",
+            "2024-01-00",
+            "1969-12-31",
+            "99999999999999-01-01",
+            "2024-01",
+            "2024-01-01-01",
+            "2024-ab-01",
+        ] {
+            assert_eq!(parse_since(date), None, "{}", date);
+        }
+    }
+
+    #[test]
+    fn parse_timestamps() {
+        assert_eq!(parse_since("1706659200"), Some(1706659200));
+    }
+
+    #[test]
+    fn parse_relative_times() {
+        // Allow the clock to advance while parsing
+        let check = |s, expected: u64| {
+            let since = parse_since(s).unwrap();
+            let diff = battery_history::now().abs_diff(since);
+            assert!(diff.abs_diff(expected) <= 1, "{}: {}", s, diff);
+        };
+
+        check("2h", 7200);
+        check("3d", 3 * 86400);
+        check("1w", 604800);
+        check("0m", 0);
+
+        // Times before the epoch start at the epoch
+        assert_eq!(parse_since("99999999999s"), Some(0));
+    }
+
+    #[test]
+    fn parse_invalid_relative_times() {
+        for time in ["", "h", "2y", "-2h", "1.5h", "2 h", "18446744073709551615w"] {
+            assert_eq!(parse_since(time), None, "{}", time);
+        }
+    }
+
+    #[test]
+    fn format_timestamps() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00");
+        assert_eq!(format_timestamp(951868799), "2000-02-29 23:59:59");
+        assert_eq!(format_timestamp(1706659200 + 3723), "2024-01-31 01:02:03");
+    }
+
+    #[test]
+    fn days_roundtrip() {
+        for days in (0..30000).step_by(7) {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+            assert!(day <= days_in_month(year, month));
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use super::socket_client::{self, SocketClient};
+use super::utils;
+use crate::daemon::unix_socket::command::{ConfigEntry, ConfigKey};
+use crate::daemon::unix_socket::Request;
+
+use clap::ArgMatches;
+
+/// Set a value
+pub fn set(sc: &mut SocketClient, app: &ArgMatches) {
+    let key = match get_key(app) {
+        Some(key) => key,
+        None => return,
+    };
+
+    let svalue = app
+        .get_one::<String>("value")
+        .map(|i| i.as_str())
+        .unwrap_or_default();
+
+    // Check value input
+    let value = match key.parse_value(svalue) {
+        Some(value) => value,
+        None => {
+            println!("invalid value: '{}' for key: '{}'", svalue, key.name());
+            return;
+        }
+    };
+
+    // Build request payload
+    let request =
+        socket_client::new_set_config_request(utils::get_device_from_app(app), key, value);
+
+    if let Some(res) = do_request(sc, app, request) {
+        print_status(&res);
+    }
+}
+
+/// Remove a value from the config, so its default gets used
+pub fn unset(sc: &mut SocketClient, app: &ArgMatches) {
+    let key = match get_key(app) {
+        Some(key) => key,
+        None => return,
+    };
+
+    let request = socket_client::new_unset_config_request(utils::get_device_from_app(app), key);
+    if let Some(res) = do_request(sc, app, request) {
+        print_status(&res);
+    }
+}
+
+/// Print a single value
+pub fn get(sc: &mut SocketClient, app: &ArgMatches) {
+    let key = match get_key(app) {
+        Some(key) => key,
+        None => return,
+    };
+
+    let request = socket_client::new_get_config_request(utils::get_device_from_app(app), key);
+    let res = match do_request(sc, app, request) {
+        Some(res) => res,
+        None => return,
+    };
+
+    let res = socket_client::to_response::<ConfigEntry>(&res);
+    if let Some(entry) = utils::unwrap_response(&res) {
+        println!("{}", format_value(&entry));
+    }
+}
+
+/// Print all values including their defaults
+pub fn list(sc: &mut SocketClient, app: &ArgMatches) {
+    let request = socket_client::new_list_config_request(utils::get_device_from_app(app));
+    let res = match do_request(sc, app, request) {
+        Some(res) => res,
+        None => return,
+    };
+
+    let res = socket_client::to_response::<Vec<ConfigEntry>>(&res);
+    for entry in utils::unwrap_response(&res).unwrap_or_default() {
+        println!("{:<28} {}", entry.key.name(), format_value(&entry));
+    }
+}
+
+// Format the value of an entry. Values which aren't set are shown with their default
+fn format_value(entry: &ConfigEntry) -> String {
+    match (&entry.value, &entry.default) {
+        (Some(value), _) => value.to_string(),
+        (None, Some(default)) => format!("{} (default)", default),
+        (None, None) => "(not set)".to_string(),
+    }
+}
+
+// Get the key passed by the user
+fn get_key(app: &ArgMatches) -> Option<ConfigKey> {
+    let skey = app.get_one::<String>("key").unwrap();
+    let key = ConfigKey::parse(skey);
+    if key.is_none() {
+        println!("Invalid key: {}", skey);
+    }
+    key
+}
+
+// Run the request. Returns the response if it has to be printed in a human readable way
+fn do_request(sc: &mut SocketClient, app: &ArgMatches, request: Request) -> Option<String> {
+    // Do unix_socket request
+    let res = match sc.do_request(request) {
+        Ok(k) => k,
+        Err(err) => {
+            eprintln!("{:?}", err);
+            return None;
+        }
+    };
+
+    // print as json if user desires so
+    if utils::print_as_json(app) {
+        println!("{}", res);
+        return None;
+    }
+
+    Some(res)
+}
+
+// Print response in a human readable way
+fn print_status(res: &str) {
+    let res = socket_client::to_response::<String>(res);
+    if res.is_success() {
+        println!("Success");
+    } else if let Some(err_msg) = res.status_message {
+        println!("Error: {}", err_msg);
+    } else {
+        println!("Error!")
+    }
+}
@@ -2,24 +2,40 @@ use super::{
     socket_client::{self, SocketClient},
     utils,
 };
+use crate::daemon::unix_socket::Request;
+
 use clap::ArgMatches;
 
 pub fn connect(sc: &mut SocketClient, app: &ArgMatches) {
-    let response = sc
-        .do_request(socket_client::new_connect_request(
-            utils::get_device_from_app(&app),
-        ))
-        .unwrap();
-
-    println!("{}", response);
+    do_request(
+        sc,
+        app,
+        socket_client::new_connect_request(utils::get_device_from_app(app)),
+    );
 }
 
 pub fn disconnect(sc: &mut SocketClient, app: &ArgMatches) {
-    let response = sc
-        .do_request(socket_client::new_disconnect_request(
-            utils::get_device_from_app(&app),
-        ))
-        .unwrap();
+    do_request(
+        sc,
+        app,
+        socket_client::new_disconnect_request(utils::get_device_from_app(app)),
+    );
+}
+
+// Run the request and print its response
+fn do_request(sc: &mut SocketClient, app: &ArgMatches, request: Request) {
+    let response = sc.do_request(request).unwrap();
+
+    // print as json if user desires so
+    if utils::print_as_json(app) {
+        println!("{}", response);
+        return;
+    }
 
-    println!("{}", response);
+    let res = socket_client::to_response::<String>(&response);
+    if res.is_success() {
+        println!("Success");
+    } else if let Some(err_msg) = res.status_message {
+        println!("Error: {}", err_msg);
+    }
 }
@@ -0,0 +1,91 @@
+use super::socket_client::{self, SocketClient};
+use super::utils;
+use crate::daemon::unix_socket::command::DeviceEntry;
+use crate::daemon::unix_socket::Request;
+
+use clap::ArgMatches;
+
+/// List all known devices
+pub fn list(sc: &mut SocketClient, app: &ArgMatches) {
+    let res = match do_request(sc, app, socket_client::new_list_devices_request()) {
+        Some(res) => res,
+        None => return,
+    };
+
+    let res = socket_client::to_response::<Vec<DeviceEntry>>(&res);
+    let devices = utils::unwrap_response(&res).unwrap_or_default();
+    if devices.is_empty() {
+        println!("No devices known yet");
+        return;
+    }
+
+    println!(
+        "{:<17}  {:<12}  {:<20}  {:<18}  {:<13}  {:<7}  Battery (L/R/C)",
+        "Address", "Alias", "Name", "Model", "Status", "Default"
+    );
+    for device in devices {
+        let status = match (device.connected, device.ready) {
+            (true, true) => "connected",
+            (true, false) => "connecting",
+            _ => "disconnected",
+        };
+
+        let battery = match (device.batt_left, device.batt_right, device.batt_case) {
+            (Some(left), Some(right), Some(case)) => format!("{}%/{}%/{}%", left, right, case),
+            _ => "-".to_string(),
+        };
+
+        println!(
+            "{:<17}  {:<12}  {:<20}  {:<18}  {:<13}  {:<7}  {}",
+            device.address,
+            device.alias.as_deref().unwrap_or("-"),
+            device.name.as_deref().unwrap_or("-"),
+            device.model.as_deref().unwrap_or("-"),
+            status,
+            if device.default { "yes" } else { "no" },
+            battery
+        );
+    }
+}
+
+/// Make a device the default device
+pub fn set_default(sc: &mut SocketClient, app: &ArgMatches) {
+    let device = app.get_one::<String>("name").unwrap().to_owned();
+    let res = match do_request(
+        sc,
+        app,
+        socket_client::new_set_default_device_request(device),
+    ) {
+        Some(res) => res,
+        None => return,
+    };
+
+    let res = socket_client::to_response::<String>(&res);
+    if res.is_success() {
+        println!("{} is the default device now", res.device);
+    } else if let Some(err_msg) = res.status_message {
+        println!("Error: {}", err_msg);
+    } else {
+        println!("Error!")
+    }
+}
+
+// Run the request. Returns the response if it has to be printed in a human readable way
+fn do_request(sc: &mut SocketClient, app: &ArgMatches, request: Request) -> Option<String> {
+    // Do unix_socket request
+    let res = match sc.do_request(request) {
+        Ok(k) => k,
+        Err(err) => {
+            eprintln!("{:?}", err);
+            return None;
+        }
+    };
+
+    // print as json if user desires so
+    if utils::print_as_json(app) {
+        println!("{}", res);
+        return None;
+    }
+
+    Some(res)
+}
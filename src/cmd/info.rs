@@ -1,8 +1,7 @@
 use super::socket_client::{self, SocketClient};
 use super::utils;
-use crate::daemon::buds_info::BudsInfoInner;
+use crate::daemon::buds_info::{BudsInfoInner, LinkState};
 
-use blurz::{BluetoothAdapter, BluetoothDevice, BluetoothSession};
 use clap::ArgMatches;
 use galaxy_buds_rs::message::bud_property::Placement;
 use galaxy_buds_rs::model::Feature::ExtTouchpadLock;
@@ -26,15 +25,48 @@ pub fn show(sc: &mut SocketClient, app: &ArgMatches) {
     let status = socket_client::to_buds_info(status);
     let res: BudsInfoInner = utils::unwrap_response(&status).unwrap();
 
-    let bt_name = get_bt_device_name(&res.address).unwrap_or_else(|| res.address.clone());
+    print_status(&res, app.get_flag("verbose"));
+}
+
+// Format an estimated amount of minutes
+fn format_minutes(minutes: Option<u32>) -> String {
+    match minutes {
+        Some(minutes) if minutes >= 60 => format!("~{}h {}min", minutes / 60, minutes % 60),
+        Some(minutes) => format!("~{}min", minutes),
+        None => "-".to_owned(),
+    }
+}
 
-    println!("Info for '{}':", bt_name);
+/// Print the status of a device in a human readable way
+pub fn print_status(res: &BudsInfoInner, verbose: bool) {
+    println!("Info for '{}':", res.name.as_ref().unwrap_or(&res.address));
     println!();
-    if app.contains_id("verbose") {
+    if verbose {
         println!("Type:\t\t{:?}", res.model);
     }
+    match res.link_state {
+        LinkState::Connected => (),
+        LinkState::Connecting => println!("Link:\t\tReconnecting"),
+        LinkState::Lost => println!("Link:\t\tLost"),
+    }
     println!("Battery:\tL: {}%, R: {}%", res.batt_left, res.batt_right);
 
+    let estimate = &res.battery_estimate;
+    if estimate.minutes_remaining_left.is_some() || estimate.minutes_remaining_right.is_some() {
+        println!(
+            "Remaining:\tL: {}, R: {}",
+            format_minutes(estimate.minutes_remaining_left),
+            format_minutes(estimate.minutes_remaining_right)
+        );
+    }
+    if estimate.minutes_to_full_left.is_some() || estimate.minutes_to_full_right.is_some() {
+        println!(
+            "Full in:\tL: {}, R: {}",
+            format_minutes(estimate.minutes_to_full_left),
+            format_minutes(estimate.minutes_to_full_right)
+        );
+    }
+
     // If one bean is not in the case, its batterystatus
     // can't be deterimned and the buds will always return 100%
     if res.placement_left == Placement::InOpenCase
@@ -106,7 +138,7 @@ pub fn show(sc: &mut SocketClient, app: &ArgMatches) {
         (res.debug.temperature_right * 100_f32).floor() / 100_f32
     );
 
-    if app.contains_id("verbose") {
+    if verbose {
         println!(
             "Current left:\t{:?}mA",
             (res.debug.current_left * 10000_f64).floor()
@@ -125,24 +157,3 @@ pub fn show(sc: &mut SocketClient, app: &ArgMatches) {
         );
     }
 }
-
-fn get_bt_device_name<S: AsRef<str>>(dev_addr: S) -> Option<String> {
-    let session = BluetoothSession::create_session(None).ok()?;
-    let adapter = BluetoothAdapter::init(&session).ok()?;
-    let devices = adapter.get_device_list().ok()?;
-
-    for i in devices.into_iter() {
-        let dev = BluetoothDevice::new(&session, i);
-        if !dev.is_connected().ok()? {
-            continue;
-        }
-
-        if dev.get_address().ok()? != dev_addr.as_ref() {
-            continue;
-        }
-
-        return Some(dev.get_name().ok()?);
-    }
-
-    None
-}
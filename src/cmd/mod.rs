@@ -1,6 +1,11 @@
-pub mod config_set;
+pub mod apply;
+pub mod battery;
+pub mod config;
 pub mod connection;
+pub mod devices;
 pub mod info;
+pub mod profile;
 pub mod set_value;
 pub mod socket_client;
 mod utils;
+pub mod watch;
@@ -0,0 +1,91 @@
+use super::apply;
+use super::socket_client::{self, SocketClient};
+use super::utils;
+use crate::daemon::buds_profile::BudsProfile;
+use crate::daemon::unix_socket::Request;
+
+use clap::ArgMatches;
+
+use std::collections::BTreeMap;
+
+/// List all stored profiles
+pub fn list(sc: &mut SocketClient, app: &ArgMatches) {
+    let request = socket_client::new_list_profiles_request(utils::get_device_from_app(app));
+    let res = match do_request(sc, app, request) {
+        Some(res) => res,
+        None => return,
+    };
+
+    let res = socket_client::to_response::<BTreeMap<String, BudsProfile>>(&res);
+    let profiles = utils::unwrap_response(&res).unwrap_or_default();
+    if profiles.is_empty() {
+        println!("No profiles stored");
+        return;
+    }
+
+    for (name, profile) in profiles {
+        println!("{}", name);
+        match profile.to_settings() {
+            Ok(settings) => settings.iter().for_each(|i| println!("\t{}", i)),
+            Err(err) => println!("\tInvalid profile: {}", err),
+        }
+    }
+}
+
+/// Store the current settings as profile
+pub fn save(sc: &mut SocketClient, app: &ArgMatches) {
+    let name = app.get_one::<String>("name").unwrap().to_owned();
+    let request = socket_client::new_save_profile_request(utils::get_device_from_app(app), name);
+    if let Some(res) = do_request(sc, app, request) {
+        print_status(&res);
+    }
+}
+
+/// Apply the settings of a profile
+pub fn load(sc: &mut SocketClient, app: &ArgMatches) {
+    let name = app.get_one::<String>("name").unwrap().to_owned();
+    let request = socket_client::new_load_profile_request(utils::get_device_from_app(app), name);
+    if let Some(res) = do_request(sc, app, request) {
+        apply::print_results(&res);
+    }
+}
+
+/// Delete a profile
+pub fn delete(sc: &mut SocketClient, app: &ArgMatches) {
+    let name = app.get_one::<String>("name").unwrap().to_owned();
+    let request = socket_client::new_delete_profile_request(utils::get_device_from_app(app), name);
+    if let Some(res) = do_request(sc, app, request) {
+        print_status(&res);
+    }
+}
+
+// Run the request. Returns the response if it has to be printed in a human readable way
+fn do_request(sc: &mut SocketClient, app: &ArgMatches, request: Request) -> Option<String> {
+    // Do unix_socket request
+    let res = match sc.do_request(request) {
+        Ok(k) => k,
+        Err(err) => {
+            eprintln!("{:?}", err);
+            return None;
+        }
+    };
+
+    // print as json if user desires so
+    if utils::print_as_json(app) {
+        println!("{}", res);
+        return None;
+    }
+
+    Some(res)
+}
+
+fn print_status(res: &str) {
+    let res = socket_client::to_response::<BudsProfile>(res);
+    if res.is_success() {
+        println!("Success");
+    } else if let Some(err_msg) = res.status_message {
+        println!("Error: {}", err_msg);
+    } else {
+        println!("Error!")
+    }
+}
@@ -1,12 +1,18 @@
 use super::socket_client::{self, SocketClient};
 use super::utils;
-use crate::daemon::utils::{is_str_bool, str_to_bool};
+use crate::daemon::unix_socket::{
+    command::{BudsSetting, ToggleKey, TouchpadFunction},
+    Request,
+};
+use crate::daemon::utils::{
+    is_str_bool, str_to_bool, str_to_equalizer, str_to_side, str_to_tap_action,
+};
 
 use clap::ArgMatches;
-use galaxy_buds_rs::message::bud_property::{BudProperty, EqualizerType, TouchpadOption};
+use galaxy_buds_rs::message::bud_property::{EqualizerType, TouchpadOption};
 
 /// Set a value
-pub fn set(sc: &mut SocketClient, app: &ArgMatches, toggle: bool, value: &str) {
+pub fn set(sc: &mut SocketClient, app: &ArgMatches, value: &str) {
     let skey = app.get_one::<String>("key").unwrap();
     let key = match Key::parse(skey) {
         Some(k) => k,
@@ -17,24 +23,50 @@ pub fn set(sc: &mut SocketClient, app: &ArgMatches, toggle: bool, value: &str) {
     };
 
     // Check value input
-    if !toggle && !is_value_ok(key, value) {
+    if !is_value_ok(key, value) {
         println!("invalid value: '{}' for key: '{}'", value, skey);
         return;
     }
 
+    // Additional input for some keys
+    let opt = if app.contains_id("opt") {
+        app.get_one::<String>("opt").map(|i| i.as_str())
+    } else {
+        None
+    };
+
+    let setting = match get_setting(key, value, opt) {
+        Ok(setting) => setting,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+
     // Build request payload
-    let mut request = socket_client::new_set_value_request(
-        utils::get_device_from_app(&app),
-        key.value(),
-        get_value(key, value),
-        toggle,
-    );
-
-    // Set opt param if present
-    if app.contains_id("opt") {
-        request.opt_param3 = app.get_one::<String>("opt").cloned();
-    }
+    let request = socket_client::new_set_value_request(utils::get_device_from_app(app), setting);
+    do_request(sc, app, request);
+}
+
+/// Toggle a value
+pub fn toggle(sc: &mut SocketClient, app: &ArgMatches) {
+    let skey = app.get_one::<String>("key").unwrap();
+    let key = match Key::parse(skey).and_then(|key| key.toggle_key()) {
+        Some(k) => k,
+        None => {
+            println!("Invalid key: {}", skey);
+            return;
+        }
+    };
+
+    // Build request payload
+    let request =
+        socket_client::new_toggle_value_request(utils::get_device_from_app(app), key, None);
+    do_request(sc, app, request);
+}
 
+// Run the request and print its response
+fn do_request(sc: &mut SocketClient, app: &ArgMatches, request: Request) {
     // Do unix_socket request
     let res = match sc.do_request(request) {
         Ok(k) => k,
@@ -45,7 +77,7 @@ pub fn set(sc: &mut SocketClient, app: &ArgMatches, toggle: bool, value: &str) {
     };
 
     // print as json if user desires so
-    if utils::print_as_json(&app) {
+    if utils::print_as_json(app) {
         println!("{}", res);
         return;
     }
@@ -61,52 +93,57 @@ pub fn set(sc: &mut SocketClient, app: &ArgMatches, toggle: bool, value: &str) {
     }
 }
 
-/// Return the actual value required for the payload
-fn get_value(key: Key, value: &str) -> String {
-    match key {
-        Key::Anc | Key::Touchpadlock => str_to_bool(value).to_string(),
-        Key::Touchpad => (!str_to_bool(value)).to_string(),
-        Key::Equalizer => parse_equalizer(value).encode().to_string(),
-        Key::TapAction => parse_tap_action(value).encode().to_string(),
-        Key::AmbientSound => value.to_string(),
-    }
+/// Return the setting to send to the daemon
+fn get_setting(key: Key, value: &str, opt: Option<&str>) -> Result<BudsSetting, String> {
+    Ok(match key {
+        Key::Anc => BudsSetting::NoiseReduction {
+            enabled: str_to_bool(value),
+        },
+        Key::Touchpadlock | Key::Touchpad => BudsSetting::LockTouchpad {
+            // 'touchpad' enables the touchpads while 'touchpadlock' locks them
+            locked: str_to_bool(value) == matches!(key, Key::Touchpadlock),
+            function: match opt {
+                Some(opt) => Some(TouchpadFunction::parse(opt).ok_or_else(|| {
+                    format!(
+                        "Unsupported touchpad function {:?}. Choose from {{tap, doubletap, tripletap, hold}}",
+                        opt
+                    )
+                })?),
+                None => None,
+            },
+        },
+        Key::Equalizer => BudsSetting::Equalizer {
+            equalizer: str_to_equalizer(value),
+        },
+        Key::TapAction => BudsSetting::TouchpadAction {
+            option: str_to_tap_action(value),
+            side: match opt {
+                Some(opt) => Some(
+                    str_to_side(opt)
+                        .ok_or_else(|| format!("Invalid side: {}", opt))?
+                        .into(),
+                ),
+                None => None,
+            },
+        },
+        Key::AmbientSound => BudsSetting::AmbientVolume {
+            volume: value
+                .parse::<u8>()
+                .map_err(|_| format!("Invalid volume level: {}", value))?,
+        },
+    })
 }
 
 /// Return true if the value is allowed for the given key
 fn is_value_ok(key: Key, value: &str) -> bool {
     match key {
         Key::Touchpadlock | Key::Touchpad | Key::Anc => is_str_bool(value),
-        Key::Equalizer => parse_equalizer(value) != EqualizerType::Undetected,
-        Key::TapAction => parse_tap_action(value) != TouchpadOption::Undetected,
+        Key::Equalizer => str_to_equalizer(value) != EqualizerType::Undetected,
+        Key::TapAction => str_to_tap_action(value) != TouchpadOption::Undetected,
         Key::AmbientSound => utils::is_number(value),
     }
 }
 
-// parse equalizer strings to enum variants
-fn parse_tap_action(value: &str) -> TouchpadOption {
-    match value.to_lowercase().as_str() {
-        "volume" => TouchpadOption::Volume,
-        "spotify" => TouchpadOption::Spotify,
-        "voice-command" => TouchpadOption::VoiceCommand,
-        "anc" => TouchpadOption::NoiseCanceling,
-        "disconnect" => TouchpadOption::Disconnect,
-        _ => TouchpadOption::Undetected,
-    }
-}
-
-// parse equalizer strings to enum variants
-fn parse_equalizer(value: &str) -> EqualizerType {
-    match value.to_lowercase().as_str() {
-        "normal" | "off" => EqualizerType::Normal,
-        "bass" | "bb" => EqualizerType::BassBoost,
-        "soft" => EqualizerType::Soft,
-        "dynamic" | "dyn" => EqualizerType::Dynamic,
-        "clear" => EqualizerType::Clear,
-        "treble" => EqualizerType::TrebleBoost,
-        _ => EqualizerType::Undetected,
-    }
-}
-
 #[derive(Debug, Copy, Clone)]
 enum Key {
     Anc,
@@ -118,14 +155,12 @@ enum Key {
 }
 
 impl Key {
-    fn value(&self) -> String {
-        String::from(match *self {
-            Key::Anc => "noise_reduction",
-            Key::Equalizer => "equalizer",
-            Key::Touchpadlock => "lock_touchpad",
-            Key::Touchpad => "lock_touchpad",
-            Key::TapAction => "touchpad_action",
-            Key::AmbientSound => "ambient_volume",
+    /// Returns the key to toggle, if the key can be toggled
+    fn toggle_key(&self) -> Option<ToggleKey> {
+        Some(match *self {
+            Key::Anc => ToggleKey::NoiseReduction,
+            Key::Touchpadlock | Key::Touchpad => ToggleKey::LockTouchpad,
+            _ => return None,
         })
     }
 
@@ -1,38 +1,76 @@
 use std::error::Error;
-use std::io::prelude::*;
+use std::io::{prelude::*, BufReader};
 use std::os::unix::net::UnixStream;
 use std::path::Path;
 
 use crate::daemon::buds_info::BudsInfoInner;
+use crate::daemon::unix_socket::command::{
+    BudsSetting, Command, ConfigKey, ConfigValue, ToggleKey, TouchpadFunction,
+};
 use crate::daemon::unix_socket::{Request, Response};
 
 pub struct SocketClient {
-    #[allow(dead_code)]
     path: String,
     socket: UnixStream,
+    reader: BufReader<UnixStream>,
 }
 
 impl SocketClient {
     // Create a new SocketClient
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let socket = UnixStream::connect(&path)?;
         Ok(Self {
             path: path.as_ref().to_str().unwrap().to_owned(),
-            socket: UnixStream::connect(path)?,
+            reader: BufReader::new(socket.try_clone()?),
+            socket,
         })
     }
 
-    /// Do a request to the daemon
+    /// Do a request to the daemon. Multiple requests
+    /// can be done using the same client
     pub fn do_request(&mut self, request: Request) -> Result<String, Box<dyn Error>> {
-        let mut stream = &self.socket;
+        self.send(request)?;
+
+        // wait for response
+        self.read_response()
+    }
+
+    /// Subscribe to buds events. Returns a reader which
+    /// yields one json encoded event per line
+    pub fn subscribe(mut self, request: Request) -> Result<BufReader<UnixStream>, Box<dyn Error>> {
+        self.send(request)?;
+
+        // The daemon confirms the subscription before sending any events
+        let response = self.read_response()?;
+        let response = to_response::<String>(&response);
+        if !response.is_success() {
+            return Err(response.status_message.unwrap_or_default().into());
+        }
+
+        Ok(self.reader)
+    }
+
+    /// Get the path of the daemon socket
+    pub fn path(&self) -> &str {
+        &self.path
+    }
 
-        // send request
+    // Send a request to the daemon
+    fn send(&mut self, request: Request) -> Result<(), Box<dyn Error>> {
+        let mut stream = &self.socket;
         stream.write_all(request.sendable()?.as_bytes())?;
         stream.flush()?;
+        Ok(())
+    }
 
-        // wait for response
+    // Read a single newline terminated response
+    fn read_response(&mut self) -> Result<String, Box<dyn Error>> {
         let mut response = String::new();
-        stream.read_to_string(&mut response)?;
-        Ok(response)
+        if self.reader.read_line(&mut response)? == 0 {
+            return Err("The daemon closed the connection".into());
+        }
+
+        Ok(response.trim_end().to_owned())
     }
 }
 
@@ -50,46 +88,93 @@ pub fn to_buds_info(response: String) -> Response<BudsInfoInner> {
 
 // Create new status request
 pub fn new_status_request(device: Option<String>) -> Request {
-    Request::new("get_status".to_owned(), device)
+    Request::new(Command::GetStatus, device)
+}
+
+// Create new subscribe request
+pub fn new_subscribe_request(device: Option<String>) -> Request {
+    Request::new(Command::Subscribe, device)
 }
 
 // Create new connect request
 pub fn new_connect_request(device: Option<String>) -> Request {
-    Request::new("connect".to_owned(), device)
+    Request::new(Command::Connect, device)
 }
 
 // Create new disconnect request
 pub fn new_disconnect_request(device: Option<String>) -> Request {
-    Request::new("disconnect".to_owned(), device)
+    Request::new(Command::Disconnect, device)
 }
 
 // Create new set_value request
-pub fn new_set_value_request(
+pub fn new_set_value_request(device: Option<String>, setting: BudsSetting) -> Request {
+    Request::new(Command::SetValue { setting }, device)
+}
+
+// Create new toggle_value request
+pub fn new_toggle_value_request(
     device: Option<String>,
-    key: String,
-    value: String,
-    toggle: bool,
+    key: ToggleKey,
+    function: Option<TouchpadFunction>,
 ) -> Request {
-    let mut request = Request::new(
-        {
-            if toggle {
-                "toggle_value"
-            } else {
-                "set_value"
-            }
-        }
-        .to_owned(),
-        device,
-    );
-    request.opt_param1 = Some(key);
-    request.opt_param2 = Some(value);
-    request
+    Request::new(Command::ToggleValue { key, function }, device)
 }
 
 // Create new set_config request
-pub fn new_set_config_request(device: Option<String>, key: String, value: String) -> Request {
-    let mut request = Request::new("set_config".to_owned(), device);
-    request.opt_param1 = Some(key);
-    request.opt_param2 = Some(value);
-    request
+pub fn new_set_config_request(
+    device: Option<String>,
+    key: ConfigKey,
+    value: ConfigValue,
+) -> Request {
+    Request::new(Command::SetConfig { key, value }, device)
+}
+
+// Create new list_devices request
+pub fn new_list_devices_request() -> Request {
+    Request::new(Command::ListDevices, None)
+}
+
+// Create new set_default_device request
+pub fn new_set_default_device_request(device: String) -> Request {
+    Request::new(Command::SetDefaultDevice, Some(device))
+}
+
+// Create new get_config request
+pub fn new_get_config_request(device: Option<String>, key: ConfigKey) -> Request {
+    Request::new(Command::GetConfig { key }, device)
+}
+
+// Create new list_config request
+pub fn new_list_config_request(device: Option<String>) -> Request {
+    Request::new(Command::ListConfig, device)
+}
+
+// Create new unset_config request
+pub fn new_unset_config_request(device: Option<String>, key: ConfigKey) -> Request {
+    Request::new(Command::UnsetConfig { key }, device)
+}
+
+// Create new apply_settings request
+pub fn new_apply_settings_request(device: Option<String>, settings: Vec<BudsSetting>) -> Request {
+    Request::new(Command::ApplySettings { settings }, device)
+}
+
+// Create new list_profiles request
+pub fn new_list_profiles_request(device: Option<String>) -> Request {
+    Request::new(Command::ListProfiles, device)
+}
+
+// Create new save_profile request
+pub fn new_save_profile_request(device: Option<String>, name: String) -> Request {
+    Request::new(Command::SaveProfile { name }, device)
+}
+
+// Create new load_profile request
+pub fn new_load_profile_request(device: Option<String>, name: String) -> Request {
+    Request::new(Command::LoadProfile { name }, device)
+}
+
+// Create new delete_profile request
+pub fn new_delete_profile_request(device: Option<String>, name: String) -> Request {
+    Request::new(Command::DeleteProfile { name }, device)
 }
@@ -0,0 +1,93 @@
+use super::info;
+use super::socket_client::{self, SocketClient};
+use super::utils;
+
+use clap::ArgMatches;
+
+use std::io::{prelude::*, ErrorKind};
+use std::process::exit;
+use std::time::Duration;
+
+/// Refresh the status at least this often, even if the buds don't report any
+/// events, to keep values like the temperatures up to date
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Continuously display the status of a device and update it on changes
+pub fn watch(sc: &mut SocketClient, app: &ArgMatches) {
+    let device = utils::get_device_from_app(app);
+    let as_json = utils::print_as_json(app);
+    let verbose = app.get_flag("verbose");
+
+    // Events are delivered over a separate connection, the
+    // existing one is used to request the status on changes
+    let events = SocketClient::new(sc.path())
+        .and_then(|client| client.subscribe(socket_client::new_subscribe_request(device.clone())));
+
+    let mut events = match events {
+        Ok(v) => v,
+        Err(err) => {
+            eprintln!("Could not subscribe to events: {}", err);
+            exit(1);
+        }
+    };
+
+    if let Err(err) = events.get_ref().set_read_timeout(Some(REFRESH_INTERVAL)) {
+        eprintln!("{:?}", err);
+        exit(1);
+    }
+
+    let mut last_status = String::new();
+
+    loop {
+        let status = match sc.do_request(socket_client::new_status_request(device.clone())) {
+            Ok(v) => v,
+            Err(err) => {
+                eprintln!("{:?}", err);
+                exit(1);
+            }
+        };
+
+        // Only print changes
+        if status != last_status {
+            if as_json {
+                println!("{}", status);
+            } else {
+                // Redraw the status in place
+                print!("\x1B[2J\x1B[1;1H");
+                print_status(&status, verbose);
+            }
+
+            last_status = status;
+        }
+
+        // Wait for the next event or the refresh interval to pass
+        let mut line = String::new();
+        match events.read_line(&mut line) {
+            Ok(0) => {
+                eprintln!("The daemon closed the connection");
+                exit(1);
+            }
+            Ok(_) => (),
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => (),
+            Err(err) => {
+                eprintln!("{:?}", err);
+                exit(1);
+            }
+        }
+    }
+}
+
+// Print a status response in a human readable way
+fn print_status(status: &str, verbose: bool) {
+    let res = socket_client::to_buds_info(status.to_owned());
+
+    let info = match res.payload {
+        Some(info) if res.is_success() => info,
+        _ => {
+            println!("{}", res.status_message.unwrap_or_default());
+            return;
+        }
+    };
+
+    info::print_status(&info, verbose);
+}
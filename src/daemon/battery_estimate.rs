@@ -0,0 +1,228 @@
+/*
+ * Estimates the remaining playtime and the time until the buds are fully
+ * charged, based on how fast the battery levels changed since the buds
+ * got put into the ears or into the case
+ */
+
+use super::buds_info::BudsInfoInner;
+
+use galaxy_buds_rs::message::bud_property::Placement;
+use serde::{Deserialize, Serialize};
+
+use std::time::SystemTime;
+
+// Minimum change of the battery level in percent required to calculate a rate
+const MIN_LEVEL_CHANGE: i8 = 2;
+
+/// Estimated minutes until the buds are empty or fully charged
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct BatteryEstimate {
+    pub minutes_remaining_left: Option<u32>,
+    pub minutes_remaining_right: Option<u32>,
+    pub minutes_to_full_left: Option<u32>,
+    pub minutes_to_full_right: Option<u32>,
+}
+
+/// Keeps track of the battery levels of both buds
+#[derive(Debug, Default)]
+pub struct BatteryTracker {
+    left: Option<Segment>,
+    right: Option<Segment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Bud is in the ear
+    Discharging,
+    /// Bud is in the case
+    Charging,
+}
+
+// A period of time in which a bud was discharging or charging without interruption
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    mode: Mode,
+    start: SystemTime,
+    start_level: i8,
+}
+
+impl BatteryTracker {
+    /// Update the estimate of a device using its current battery levels and placements
+    pub fn update(&mut self, info: &mut BudsInfoInner) {
+        let now = SystemTime::now();
+
+        let (remaining, to_full) =
+            update_bud(&mut self.left, info.batt_left, info.placement_left, now);
+        info.battery_estimate.minutes_remaining_left = remaining;
+        info.battery_estimate.minutes_to_full_left = to_full;
+
+        let (remaining, to_full) =
+            update_bud(&mut self.right, info.batt_right, info.placement_right, now);
+        info.battery_estimate.minutes_remaining_right = remaining;
+        info.battery_estimate.minutes_to_full_right = to_full;
+    }
+}
+
+// Update the segment of a single bud at the time 'now'. Returns the
+// estimated minutes remaining and the estimated minutes to full
+fn update_bud(
+    segment: &mut Option<Segment>,
+    level: i8,
+    placement: Placement,
+    now: SystemTime,
+) -> (Option<u32>, Option<u32>) {
+    let mode = match placement {
+        Placement::Ear => Mode::Discharging,
+        Placement::InOpenCase | Placement::InCloseCase => Mode::Charging,
+        _ => {
+            *segment = None;
+            return (None, None);
+        }
+    };
+
+    // Start a new segment if the bud changed its mode
+    let current = match *segment {
+        Some(current) if current.mode == mode => current,
+        _ => {
+            *segment = Some(Segment {
+                mode,
+                start: now,
+                start_level: level,
+            });
+            return (None, None);
+        }
+    };
+
+    let changed = match mode {
+        Mode::Discharging => current.start_level - level,
+        Mode::Charging => level - current.start_level,
+    };
+
+    // The level moved in the wrong direction, so the segment is useless
+    if changed < 0 {
+        *segment = None;
+        return (None, None);
+    }
+
+    if changed < MIN_LEVEL_CHANGE {
+        return (None, None);
+    }
+
+    let minutes = now
+        .duration_since(current.start)
+        .unwrap_or_default()
+        .as_secs_f64()
+        / 60.0;
+    if minutes <= 0.0 {
+        return (None, None);
+    }
+
+    // Percent per minute
+    let rate = changed as f64 / minutes;
+
+    match mode {
+        Mode::Discharging => (Some((level.max(0) as f64 / rate) as u32), None),
+        Mode::Charging => (None, Some(((100 - level).max(0) as f64 / rate) as u32)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    const EAR: Placement = Placement::Ear;
+    const CASE: Placement = Placement::InOpenCase;
+
+    // Feed the samples (minute, level, placement) in order and
+    // collect the estimated minutes remaining and to full
+    fn estimate(samples: &[(u64, i8, Placement)]) -> Vec<(Option<u32>, Option<u32>)> {
+        let mut segment = None;
+        samples
+            .iter()
+            .map(|(minute, level, placement)| {
+                let now = SystemTime::UNIX_EPOCH + Duration::from_secs(minute * 60);
+                update_bud(&mut segment, *level, *placement, now)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn discharging() {
+        // 10% in 10 minutes
+        assert_eq!(
+            estimate(&[(0, 90, EAR), (10, 80, EAR), (20, 70, EAR)]),
+            vec![(None, None), (Some(80), None), (Some(70), None)]
+        );
+    }
+
+    #[test]
+    fn charging() {
+        // 20% in 10 minutes
+        assert_eq!(
+            estimate(&[(0, 40, CASE), (10, 60, CASE)]),
+            vec![(None, None), (None, Some(20))]
+        );
+    }
+
+    #[test]
+    fn mode_change_resets_segment() {
+        assert_eq!(
+            estimate(&[
+                (0, 90, EAR),
+                (10, 80, EAR),
+                // Charging starts a new segment
+                (20, 80, CASE),
+                (30, 90, CASE),
+                // Taking the bud out of the case and the ear too
+                (40, 90, Placement::Outside),
+                (50, 80, EAR),
+            ]),
+            vec![
+                (None, None),
+                (Some(80), None),
+                (None, None),
+                (None, Some(10)),
+                (None, None),
+                (None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn wrong_direction_resets_segment() {
+        assert_eq!(
+            estimate(&[
+                (0, 80, EAR),
+                // Rising while discharging drops the segment
+                (10, 81, EAR),
+                // and the next sample starts a new one
+                (20, 79, EAR),
+                (24, 77, EAR),
+            ]),
+            vec![(None, None), (None, None), (None, None), (Some(154), None)]
+        );
+
+        // Falling while charging
+        assert_eq!(
+            estimate(&[(0, 50, CASE), (10, 49, CASE), (20, 60, CASE)]),
+            vec![(None, None), (None, None), (None, None)]
+        );
+    }
+
+    #[test]
+    fn minimum_level_change() {
+        // A single percent isn't enough for an estimate
+        assert_eq!(
+            estimate(&[(0, 90, EAR), (1, 89, EAR), (4, 88, EAR)]),
+            vec![(None, None), (None, None), (Some(176), None)]
+        );
+
+        // Neither is no change at all
+        assert_eq!(
+            estimate(&[(0, 90, EAR), (30, 90, EAR)]),
+            vec![(None, None), (None, None)]
+        );
+    }
+}
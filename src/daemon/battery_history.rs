@@ -0,0 +1,128 @@
+/*
+ * Persists the battery levels of the buds over time
+ */
+
+use super::buds_config::{get_home_dir, get_xdg_data};
+use super::buds_info::{placement_dser, BudsInfoInner};
+
+use async_std::{fs::OpenOptions, io::prelude::*, path::PathBuf};
+use galaxy_buds_rs::message::bud_property::Placement;
+use serde::{Deserialize, Serialize};
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Battery levels of a device at a given time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatterySample {
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+    pub address: String,
+    pub left: i8,
+    pub right: i8,
+    pub case: i8,
+    #[serde(with = "placement_dser")]
+    pub placement_left: Placement,
+    #[serde(with = "placement_dser")]
+    pub placement_right: Placement,
+}
+
+impl BatterySample {
+    /// Create a sample of the current state of a device
+    pub fn from_info(info: &BudsInfoInner) -> Self {
+        Self {
+            timestamp: now(),
+            address: info.address.clone(),
+            left: info.batt_left,
+            right: info.batt_right,
+            case: info.batt_case,
+            placement_left: info.placement_left,
+            placement_right: info.placement_right,
+        }
+    }
+
+    /// Returns true if the battery levels or placements of both samples differ
+    pub fn differs(&self, other: &BatterySample) -> bool {
+        self.left != other.left
+            || self.right != other.right
+            || self.case != other.case
+            || self.placement_left != other.placement_left
+            || self.placement_right != other.placement_right
+    }
+}
+
+/// Append a sample to the history file
+pub async fn append(sample: &BatterySample) -> Result<(), String> {
+    let file = get_history_file()?;
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+
+    let mut line = serde_json::to_string(sample).map_err(|e| e.to_string())?;
+    line.push('\n');
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&file)
+        .await
+        .map_err(|e| e.to_string())?
+        .write_all(line.as_bytes())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Load all samples, optionally only of a given device and/or newer than 'since'
+pub fn load(address: Option<&str>, since: Option<u64>) -> Result<Vec<BatterySample>, String> {
+    let file = match File::open(get_history_file()?) {
+        Ok(file) => file,
+        // No samples recorded yet
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err.to_string()),
+    };
+
+    let mut samples = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| e.to_string())?;
+
+        // Skip broken lines, e.g. from an interrupted write
+        let sample: BatterySample = match serde_json::from_str(&line) {
+            Ok(sample) => sample,
+            Err(_) => continue,
+        };
+
+        if let Some(address) = address {
+            if !sample.address.eq_ignore_ascii_case(address) {
+                continue;
+            }
+        }
+
+        if since.map(|since| sample.timestamp < since).unwrap_or(false) {
+            continue;
+        }
+
+        samples.push(sample);
+    }
+
+    Ok(samples)
+}
+
+// Get the file the history is stored in
+fn get_history_file() -> Result<PathBuf, String> {
+    let data_home = get_xdg_data()
+        .or_else(|| get_home_dir().map(|i| i.join(".local").join("share")))
+        .ok_or_else(|| "Can't determine home directory!".to_string())?;
+
+    Ok(data_home.join("livebuds").join("battery_history.jsonl"))
+}
+
+/// Seconds since the unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|i| i.as_secs())
+        .unwrap_or_default()
+}
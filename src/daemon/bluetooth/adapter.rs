@@ -0,0 +1,57 @@
+/*
+ * Selects the bluetooth adapter configured by the user
+ */
+
+use super::bt_connection_listener::BtError;
+
+use blurz::{BluetoothAdapter, BluetoothSession};
+
+// BlueZ doesn't offer a way to look up adapters by their address,
+// so the adapters hci0 up to this number get checked
+const MAX_ADAPTERS: u8 = 16;
+
+/// Get the adapter to use. The adapter can be given by its name (e.g. hci1) or its
+/// address. If no adapter is given, the first adapter found by BlueZ is used
+pub fn get_adapter<'a>(
+    session: &'a BluetoothSession,
+    adapter: Option<&str>,
+) -> Result<BluetoothAdapter<'a>, BtError> {
+    let adapter = match adapter {
+        Some(adapter) => adapter,
+        None => return BluetoothAdapter::init(session).map_err(BtError::from_adapter_err),
+    };
+
+    if !adapter.contains(':') {
+        return get_by_name(session, adapter).ok_or(BtError::AdapterMissing);
+    }
+
+    (0..MAX_ADAPTERS)
+        .filter_map(|i| get_by_name(session, &format!("hci{}", i)))
+        .find(|i| {
+            i.get_address()
+                .map(|i| i.eq_ignore_ascii_case(adapter))
+                .unwrap_or(false)
+        })
+        .ok_or(BtError::AdapterMissing)
+}
+
+// Get an adapter by its name. Returns None if the adapter doesn't exist
+fn get_by_name<'a>(session: &'a BluetoothSession, name: &str) -> Option<BluetoothAdapter<'a>> {
+    let adapter = BluetoothAdapter::create_adapter(session, format!("/org/bluez/{}", name)).ok()?;
+
+    // Creating an adapter always succeeds, reading from it only if it exists
+    adapter.get_address().ok()?;
+    Some(adapter)
+}
+
+/// Check whether a device is connected via the given adapter
+pub fn has_device(adapter: &BluetoothAdapter, device_path: &str) -> bool {
+    is_on_adapter(device_path, &adapter.get_id())
+}
+
+/// Check whether an object path belongs to the adapter with the given object path
+pub fn is_on_adapter(path: &str, adapter_path: &str) -> bool {
+    path.strip_prefix(adapter_path)
+        .map(|i| i.starts_with('/'))
+        .unwrap_or(false)
+}
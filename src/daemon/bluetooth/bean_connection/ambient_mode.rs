@@ -1,6 +1,16 @@
+use super::super::super::buds_config::Config;
 use super::super::super::buds_info::BudsInfo;
+use super::super::super::notification::{self, NotificationEvent};
+
+use async_std::sync::{Arc, Mutex};
 use galaxy_buds_rs::message::ambient_mode::AmbientModeUpdated;
 
-pub fn handle(update: AmbientModeUpdated, info: &mut BudsInfo) {
+pub async fn handle(update: AmbientModeUpdated, info: &mut BudsInfo, config: &Arc<Mutex<Config>>) {
+    let changed = info.inner.ambient_sound_enabled != update.ambient_mode;
     info.inner.ambient_sound_enabled = update.ambient_mode;
+
+    if changed {
+        let event = NotificationEvent::AmbientSound(update.ambient_mode);
+        notification::notify(event, &info.inner.address, info.inner.model, config).await;
+    }
 }
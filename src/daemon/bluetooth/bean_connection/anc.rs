@@ -1,6 +1,16 @@
+use super::super::super::buds_config::Config;
 use super::super::super::buds_info::BudsInfo;
+use super::super::super::notification::{self, NotificationEvent};
+
+use async_std::sync::{Arc, Mutex};
 use galaxy_buds_rs::message::anc_updated::AncModeUpdated;
 
-pub fn handle(update: AncModeUpdated, info: &mut BudsInfo) {
+pub async fn handle(update: AncModeUpdated, info: &mut BudsInfo, config: &Arc<Mutex<Config>>) {
+    let changed = info.inner.noise_reduction != update.anc_enabled;
     info.inner.noise_reduction = update.anc_enabled;
+
+    if changed {
+        let event = NotificationEvent::Anc(update.anc_enabled);
+        notification::notify(event, &info.inner.address, info.inner.model, config).await;
+    }
 }
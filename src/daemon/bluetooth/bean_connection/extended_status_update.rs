@@ -4,6 +4,7 @@ use galaxy_buds_rs::{message::extended_status_updated::ExtendedStatusUpdate, mod
 pub fn handle(update: ExtendedStatusUpdate, info: &mut BudsInfo) {
     // Update values from extended update
     update_extended_status(update, info);
+    info.battery_tracker.update(&mut info.inner);
 
     // Set ready after first extended status update
     if !info.inner.ready {
@@ -0,0 +1,176 @@
+/*
+ * Reassembles frames from the raw RFCOMM byte stream. A single read
+ * can contain a partial frame, multiple frames or garbage in between
+ */
+
+use galaxy_buds_rs::model::Model;
+
+// Bytes around the id, payload and crc: SOM, 2 header bytes and EOM
+const FRAME_OVERHEAD: usize = 4;
+
+// Every frame contains at least the id and the crc
+const MIN_PAYLOAD_SIZE: usize = 3;
+
+/// Buffers received bytes and yields complete frames
+#[derive(Debug)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+    som: u8,
+    eom: u8,
+    legacy_header: bool,
+}
+
+impl FrameDecoder {
+    pub fn new(model: Model) -> Self {
+        // The original buds use different frame markers and headers
+        let legacy = model == Model::Buds;
+
+        Self {
+            buffer: Vec::new(),
+            som: if legacy { 0xFE } else { 0xFD },
+            eom: if legacy { 0xEE } else { 0xDD },
+            legacy_header: legacy,
+        }
+    }
+
+    /// Add received bytes
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Get the next complete frame. Returns None if more data is required
+    pub fn next_frame(&mut self) -> Option<Vec<u8>> {
+        loop {
+            // Drop everything in front of the next start of a frame
+            match self.buffer.iter().position(|i| *i == self.som) {
+                Some(pos) => {
+                    self.buffer.drain(..pos);
+                }
+                None => {
+                    self.buffer.clear();
+                    return None;
+                }
+            }
+
+            let frame_len = match self.frame_len(0) {
+                Some(len) => len,
+                None if self.buffer.len() < 3 => return None,
+                // The header can't belong to a frame, so the start byte was garbage
+                None => {
+                    self.buffer.remove(0);
+                    continue;
+                }
+            };
+
+            if self.buffer.len() < frame_len {
+                // A start byte in garbage can claim a length of up to 1027 bytes. Instead
+                // of waiting for that much data, skip it once a complete frame follows
+                if self.has_frame_after(0) {
+                    self.buffer.remove(0);
+                    continue;
+                }
+                return None;
+            }
+
+            // If the frame doesn't end where the header claims, the start
+            // byte was part of garbage. Skip it and search for the next one
+            if self.buffer[frame_len - 1] != self.eom {
+                self.buffer.remove(0);
+                continue;
+            }
+
+            return Some(self.buffer.drain(..frame_len).collect());
+        }
+    }
+
+    // Length of the frame starting at 'pos' as stated in its header. Returns
+    // None if the header is incomplete or too short for the id and crc
+    fn frame_len(&self, pos: usize) -> Option<usize> {
+        let header = self.buffer.get(pos..pos + 3)?;
+
+        let size = if self.legacy_header {
+            header[2] as usize
+        } else {
+            (header[1] as usize | (header[2] as usize) << 8) & 0x3FF
+        };
+
+        (size >= MIN_PAYLOAD_SIZE).then(|| size + FRAME_OVERHEAD)
+    }
+
+    // Check whether a complete frame starts somewhere after 'pos'
+    fn has_frame_after(&self, pos: usize) -> bool {
+        (pos + 1..self.buffer.len())
+            .filter(|i| self.buffer[*i] == self.som)
+            .any(|i| match self.frame_len(i) {
+                Some(len) => self.buffer.get(i + len - 1) == Some(&self.eom),
+                None => false,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::fake_buds::encode_frame;
+    use super::*;
+
+    fn decode(decoder: &mut FrameDecoder) -> Vec<Vec<u8>> {
+        std::iter::from_fn(|| decoder.next_frame()).collect()
+    }
+
+    #[test]
+    fn split_frame() {
+        let frame = encode_frame(0x61, &[1, 2, 3, 4], Model::BudsLive);
+        let mut decoder = FrameDecoder::new(Model::BudsLive);
+
+        for byte in &frame[..frame.len() - 1] {
+            decoder.push(&[*byte]);
+            assert_eq!(decoder.next_frame(), None);
+        }
+
+        decoder.push(&frame[frame.len() - 1..]);
+        assert_eq!(decoder.next_frame(), Some(frame));
+        assert_eq!(decoder.next_frame(), None);
+    }
+
+    #[test]
+    fn merged_frames() {
+        let first = encode_frame(0x61, &[1, 2, 3], Model::Buds);
+        let second = encode_frame(0x60, &[4, 5], Model::Buds);
+        let mut decoder = FrameDecoder::new(Model::Buds);
+
+        decoder.push(&[first.clone(), second.clone(), second[..2].to_vec()].concat());
+        assert_eq!(decode(&mut decoder), vec![first, second.clone()]);
+
+        decoder.push(&second[2..]);
+        assert_eq!(decode(&mut decoder), vec![second]);
+    }
+
+    #[test]
+    fn garbage_prefix() {
+        let frame = encode_frame(0x61, &[1, 2, 3], Model::BudsPro);
+        let mut decoder = FrameDecoder::new(Model::BudsPro);
+
+        // Garbage containing start and end bytes
+        decoder.push(&[0x00, 0xDD, 0xFD, 0x01, 0x00, 0xFD, 0x12]);
+        decoder.push(&frame);
+        assert_eq!(decode(&mut decoder), vec![frame]);
+    }
+
+    #[test]
+    fn bogus_length_prefix() {
+        let frame = encode_frame(0x61, &[1, 2, 3], Model::BudsLive);
+        let mut decoder = FrameDecoder::new(Model::BudsLive);
+
+        // A start byte claiming a frame of 1027 bytes
+        decoder.push(&[0xFD, 0xFF, 0x03]);
+        assert_eq!(decoder.next_frame(), None);
+
+        decoder.push(&frame);
+        assert_eq!(decode(&mut decoder), vec![frame.clone()]);
+
+        // Headers too short for the id and crc
+        decoder.push(&[0xFD, 0x00, 0x00, 0xFD, 0x02, 0x00]);
+        decoder.push(&frame);
+        assert_eq!(decode(&mut decoder), vec![frame]);
+    }
+}
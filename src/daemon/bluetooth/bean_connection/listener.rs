@@ -1,152 +1,241 @@
 use super::{
     super::{
-        super::{buds_config::Config, buds_info::BudsInfo},
+        super::{
+            battery_history,
+            buds_config::Config,
+            buds_event::BudsEvent,
+            buds_info::{BudsInfo, LinkState},
+            capture::{Direction, Recorder},
+            unix_socket::command::TouchpadSide,
+        },
         bt_connection_listener::BudsConnection,
         rfcomm_connector::ConnHandler,
     },
-    ambient_mode, anc, extended_status_update, get_all_data, status_update, touchpad,
+    ambient_mode, anc, extended_status_update,
+    frame_decoder::FrameDecoder,
+    get_all_data, restore, status_update, touchpad,
 };
 
 use async_std::{io::prelude::*, sync::Mutex};
 use galaxy_buds_rs::{
-    message::{self, debug::GetAllData, ids, usage_report::UsageReport, Message, Payload},
+    message::{
+        self, bud_property::Side, debug::GetAllData, ids, touchpad_action::TouchAction,
+        usage_report::UsageReport, Message, Payload,
+    },
     model::Model,
 };
 
-use std::{process::exit, sync::Arc};
+use std::sync::Arc;
 
 const BUFF_SIZE: usize = 2048;
 
+/// The reason the listener stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListenerExit {
+    /// Reading from the device failed
+    LinkLost,
+    /// The device got disconnected on purpose and was removed already
+    Disconnected,
+}
+
 /// Read buds data
 pub async fn start_listen(
     connection: BudsConnection,
     config: Arc<Mutex<Config>>,
     ch: Arc<Mutex<ConnHandler>>,
     model: Model,
-) {
+    recorder: Option<Arc<Recorder>>,
+) -> ListenerExit {
     let mut stream = connection.socket.get_stream();
     let mut buffer: Vec<u8> = vec![0u8; BUFF_SIZE];
 
-    // Check config errors
+    // Use the new stream if the device got reconnected
     {
-        let mut cfg = config.lock().await;
-        if let Err(err) = cfg.load().await {
-            eprintln!("{}", err);
-            exit(1);
+        let connection_handler = ch.lock().await;
+        let mut lock = connection_handler.connection_data.lock().await;
+        let connection_data = &mut *lock;
+
+        if let Some(info) = connection_data.data.get_mut(&connection.addr) {
+            let old_state = info.inner.clone();
+            info.stream = stream.clone();
+            info.inner.link_state = LinkState::Connected;
+            connection_data
+                .subscribers
+                .publish_changes(&old_state, &info.inner);
         }
     }
 
     let mut requested_debug = false;
-    let mut first_msg = true;
+    let mut decoder = FrameDecoder::new(model);
 
     loop {
         let bytes_read = match stream.read(&mut buffer).await {
-            Ok(v) => v,
-            Err(_) => {
-                let mut c = ch.lock().await;
-                c.remove_device(connection.addr.as_str()).await;
-                return;
-            }
+            Ok(v) if v > 0 => v,
+            _ => return ListenerExit::LinkLost,
         };
 
-        // The received message from the buds
-        let message = Message::new(&buffer[0..bytes_read], model);
-
-        if !message.is_message() {
-            first_msg = true;
-            continue;
+        if let Some(ref recorder) = recorder {
+            recorder.record(
+                &connection.addr,
+                model,
+                Direction::In,
+                &buffer[0..bytes_read],
+            );
         }
 
-        // validate crc checksum
-        if !message.check_crc() {
-            // First received message always throws an CRC error. Since its nothing important we
-            // can igonre it. However we don't want and need it to print an error.
-            if !first_msg {
-                println!("WARNING: CRC failed. Skipping message");
+        // Handle every complete frame received so far
+        decoder.push(&buffer[0..bytes_read]);
+        while let Some(frame) = decoder.next_frame() {
+            // The received message from the buds
+            let message = Message::new(&frame, model);
+
+            if !message.is_message() {
+                continue;
             }
-            first_msg = false;
-            continue;
-        }
 
-        // Use a variable to store whether the connection should be closed at the end of the
-        // following scope. This is necessary because the 'lock' can't be borrowed twice at the
-        // same time. Yes, I do hate me for this.
-        let mut disconnect_afterwards = false;
+            // validate crc checksum
+            if !message.check_crc() {
+                println!("WARNING: CRC failed. Skipping message");
+                continue;
+            }
 
-        {
-            let connection_handler = ch.lock().await;
-            let mut lock = connection_handler.connection_data.lock().await;
+            // Use a variable to store whether the connection should be closed at the end of the
+            // following scope. This is necessary because the 'lock' can't be borrowed twice at the
+            // same time. Yes, I do hate me for this.
+            let mut disconnect_afterwards = false;
+
+            // Data to write to disk once the locks are released
+            let mut battery_sample = None;
+            let mut last_state = None;
+
+            {
+                let connection_handler = ch.lock().await;
+                let mut lock = connection_handler.connection_data.lock().await;
+                let connection_data = &mut *lock;
+
+                let info = connection_data
+                    .data
+                    .entry(connection.addr.clone())
+                    .or_insert_with(|| {
+                        let mut info = BudsInfo::new(stream.clone(), &connection.addr, model);
+                        info.recorder = recorder.clone();
+                        info
+                    });
+
+                // Remember the previous state to notify subscribers about changes
+                let old_state = info.inner.clone();
+                let old_low_battery = info.low_battery;
+
+                match message.get_id() {
+                    ids::TOUCHPAD_ACTION => {
+                        let action: TouchAction = message.into();
+                        connection_data
+                            .subscribers
+                            .publish(BudsEvent::TouchpadAction {
+                                address: connection.addr.clone(),
+                                side: match action.side {
+                                    Side::Left => TouchpadSide::Left,
+                                    Side::Right => TouchpadSide::Right,
+                                },
+                                touch_count: action.touch_count,
+                            });
+
+                        if touchpad::handle(action, info, &config, &connection).await {
+                            disconnect_afterwards = true;
+                        }
+                    }
 
-            let info = lock
-                .data
-                .entry(connection.addr.clone())
-                .or_insert_with(|| BudsInfo::new(stream.clone(), &connection.addr, model));
+                    ids::STATUS_UPDATED => {
+                        battery_sample =
+                            status_update::handle(message.into(), info, &config, &connection).await;
+                    }
 
-            match message.get_id() {
-                ids::TOUCHPAD_ACTION => {
-                    if touchpad::handle(message.into(), info, &config, &connection).await {
-                        disconnect_afterwards = true;
+                    ids::EXTENDED_STATUS_UPDATED => {
+                        extended_status_update::handle(message.into(), info);
+
+                        // Respond with set manager
+                        let data = message::manager::new(true, 24).get_data();
+                        if let Some(ref recorder) = recorder {
+                            recorder.record(&connection.addr, model, Direction::Out, &data);
+                        }
+                        stream.write(&data).await.unwrap();
+
+                        // Re-apply the last known settings once the device is ready
+                        // or retry it, if it failed the last time
+                        if (!old_state.ready || info.restore_failed) && info.inner.ready {
+                            restore::restore_settings(info, &config).await;
+                        }
                     }
-                }
 
-                ids::STATUS_UPDATED => {
-                    status_update::handle(message.into(), info, &config, &connection).await
-                }
+                    ids::DEBUG_GET_ALL_DATA => {
+                        let dbg_data: Option<GetAllData> = message.into();
+                        if let Some(data) = dbg_data {
+                            get_all_data::handle(data, info);
+                        }
+                    }
 
-                ids::EXTENDED_STATUS_UPDATED => {
-                    extended_status_update::handle(message.into(), info);
+                    ids::AMBIENT_MODE_UPDATED => {
+                        ambient_mode::handle(message.into(), info, &config).await;
+                    }
 
-                    // Respond with set manager
-                    stream
-                        .write(&message::manager::new(true, 24).get_data())
-                        .await
-                        .unwrap();
-                }
+                    ids::NOISE_REDUCTION_MODE_UPDATE => {
+                        anc::handle(message.into(), info, &config).await;
+                    }
 
-                ids::DEBUG_GET_ALL_DATA => {
-                    let dbg_data: Option<GetAllData> = message.into();
-                    if let Some(data) = dbg_data {
-                        get_all_data::handle(data, info);
+                    ids::USAGE_REPORT => {
+                        let report = UsageReport::new(message.get_payload_bytes());
+                        println!("{report:#?}");
                     }
-                }
 
-                ids::AMBIENT_MODE_UPDATED => {
-                    ambient_mode::handle(message.into(), info);
-                }
+                    _ => (),
+                };
 
-                ids::NOISE_REDUCTION_MODE_UPDATE => {
-                    anc::handle(message.into(), info);
+                connection_data
+                    .subscribers
+                    .publish_changes(&old_state, &info.inner);
+                for event in
+                    BudsEvent::from_low_battery(&old_low_battery, &info.low_battery, &info.inner)
+                {
+                    connection_data.subscribers.publish(event);
                 }
 
-                ids::USAGE_REPORT => {
-                    let report = UsageReport::new(message.get_payload_bytes());
-                    println!("{report:#?}");
+                // Remember changed settings. Skip the initial state since it might have
+                // been reset by the buds and keep the settings which couldn't be restored
+                if old_state.ready && !info.restore_failed {
+                    last_state = Some(info.inner.clone());
                 }
 
-                _ => (),
-            };
+                // Send debug request at an appropriate interval
+                if !requested_debug || info.last_debug.elapsed().unwrap_or_default().as_secs() >= 8
+                {
+                    if let Err(err) = info.request_debug_data().await {
+                        println!("Error sending debug request {:?}", err);
+                    }
+                }
 
-            // Send debug request at an appropriate interval
-            if !requested_debug || info.last_debug.elapsed().unwrap_or_default().as_secs() >= 8 {
-                if let Err(err) = info.request_debug_data().await {
-                    println!("Error sending debug request {:?}", err);
+                if !requested_debug {
+                    requested_debug = true;
                 }
             }
 
-            if !requested_debug {
-                requested_debug = true;
+            if let Some(sample) = battery_sample {
+                if let Err(err) = battery_history::append(&sample).await {
+                    eprintln!("Can't save battery history: {}", err);
+                }
             }
-        }
 
-        if first_msg {
-            first_msg = false;
-        }
+            if let Some(state) = last_state {
+                if let Err(err) = config.lock().await.save_last_state(&state).await {
+                    eprintln!("Err saving config: {}", err);
+                }
+            }
 
-        // Disconnect from device
-        if disconnect_afterwards {
-            println!("Disconnecting from device {}", connection.addr);
-            ch.lock().await.remove_device(&connection.addr).await;
-            return;
+            // Disconnect from device
+            if disconnect_afterwards {
+                println!("Disconnecting from device {}", connection.addr);
+                ch.lock().await.remove_device(&connection.addr).await;
+                return ListenerExit::Disconnected;
+            }
         }
     }
 }
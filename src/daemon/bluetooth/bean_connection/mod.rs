@@ -1,8 +1,10 @@
 mod ambient_mode;
 mod anc;
 mod extended_status_update;
+mod frame_decoder;
 mod get_all_data;
 pub mod listener;
+mod restore;
 mod sink;
 mod status_update;
 mod touchpad;
@@ -0,0 +1,44 @@
+use super::super::super::{
+    buds_config::Config, buds_info::BudsInfo, buds_profile::BudsProfile, unix_socket::set_value,
+};
+
+use async_std::sync::Mutex;
+
+use std::sync::Arc;
+
+/// Send the settings a device had when it was used the last time, if the user enabled
+/// restoring them. If that fails, 'restore_failed' gets set to keep the stored settings
+pub async fn restore_settings(info: &mut BudsInfo, config: &Arc<Mutex<Config>>) {
+    info.restore_failed = false;
+
+    let last_state = {
+        let cfg = config.lock().await;
+        cfg.get_device_config(&info.inner.address)
+            .filter(|i| i.restore_settings())
+            .and_then(|i| i.last_state.clone())
+    };
+
+    let last_state = match last_state {
+        Some(state) => state,
+        None => return,
+    };
+
+    // Nothing to do if the buds still know the settings
+    if BudsProfile::from_info(&info.inner) == last_state {
+        return;
+    }
+
+    // Invalid stored settings can't be restored later on either
+    let settings = match last_state.to_settings() {
+        Ok(settings) => settings,
+        Err(err) => {
+            eprintln!("Can't restore settings: {}", err);
+            return;
+        }
+    };
+
+    if let (_, Some(err)) = set_value::apply_settings(&settings, info).await {
+        eprintln!("Can't restore settings: {}", err.message);
+        info.restore_failed = true;
+    }
+}
@@ -1,7 +1,12 @@
 use super::utils;
+use crate::daemon::buds_config::{BudsConfig, NotificationUrgency};
 use crate::daemon::buds_info::BudsInfo;
 
 use galaxy_buds_rs::message::status_updated::StatusUpdate;
+use notify_rust::{Notification, Timeout, Urgency};
+
+// Percent the battery has to rise above the highest threshold to notify again
+const LOW_BATTERY_HYSTERESIS: i8 = 10;
 
 #[cfg(feature = "pulse-sink")]
 use pulsectl::controllers::{types::DeviceInfo, DeviceControl, SinkController};
@@ -20,27 +25,82 @@ pub fn make_sink_default(info: &BudsInfo) -> Option<()> {
     None
 }
 
-pub fn handle_low_battery(update: &StatusUpdate, info: &mut BudsInfo) {
-    let l_batt = update.battery_left;
-    let r_batt = update.battery_right;
+/// Keep track of the battery of the buds and the case dropping below one of the
+/// configured thresholds and show a notification if enabled
+pub fn handle_low_battery(update: &StatusUpdate, info: &mut BudsInfo, config: &BudsConfig) {
+    // Buds which aren't connected report 0%, so ignore them
+    let buds_level = [update.battery_left, update.battery_right]
+        .iter()
+        .filter(|i| **i > 0)
+        .min()
+        .copied();
+
+    if let Some(level) = buds_level {
+        let thresholds = config.low_battery_thresholds();
+        if let Some(threshold) = crossed_threshold(level, &thresholds, &mut info.low_battery.buds) {
+            let notification = utils::get_desktop_notification(
+                info.inner.model,
+                update.battery_left,
+                update.battery_right,
+                threshold,
+            );
+            if config.low_battery_notification() {
+                show_notification(notification, config);
+            }
+        }
+    }
+
+    // The case only reports its battery while a bud is inside
+    let in_case = utils::is_some_in_case_state(update.placement_left, update.placement_right);
+    if in_case && update.battery_case > 0 {
+        let thresholds = config.low_battery_case_thresholds();
+        if let Some(threshold) =
+            crossed_threshold(update.battery_case, &thresholds, &mut info.low_battery.case)
+        {
+            let notification =
+                utils::get_case_notification(info.inner.model, update.battery_case, threshold);
+            if config.low_battery_notification() {
+                show_notification(notification, config);
+            }
+        }
+    }
+
+    info.inner.did_battery_notify =
+        info.low_battery.buds.is_some() || info.low_battery.case.is_some();
+}
+
+// Returns the threshold to notify about if 'level' dropped below a threshold
+// lower than the last notified one. Resets once the level rose again
+fn crossed_threshold(level: i8, thresholds: &[i8], notified: &mut Option<i8>) -> Option<i8> {
+    let highest = *thresholds.iter().max()?;
+    if level > highest.saturating_add(LOW_BATTERY_HYSTERESIS) {
+        *notified = None;
+        return None;
+    }
 
-    // Reset battery notify lock
-    if l_batt > 30 && r_batt > 30 && info.inner.did_battery_notify {
-        info.inner.did_battery_notify = false;
-        return;
+    // The lowest threshold the level is below of
+    let threshold = *thresholds.iter().filter(|i| level < **i).min()?;
+    if notified.map(|i| threshold >= i).unwrap_or(false) {
+        return None;
     }
 
-    // Check if already notified
-    if info.inner.did_battery_notify {
-        return;
+    *notified = Some(threshold);
+    Some(threshold)
+}
+
+fn show_notification(mut notification: Notification, config: &BudsConfig) {
+    notification.urgency(match config.low_battery_urgency() {
+        NotificationUrgency::Low => Urgency::Low,
+        NotificationUrgency::Normal => Urgency::Normal,
+        NotificationUrgency::Critical => Urgency::Critical,
+    });
+
+    if let Some(timeout) = config.low_battery_timeout {
+        notification.timeout(Timeout::Milliseconds(timeout));
     }
 
-    // Display a notification below 20% (both have to be above 0%)
-    if l_batt < 20 || r_batt < 20 && (l_batt * r_batt > 0) {
-        info.inner.did_battery_notify = true;
-        utils::get_desktop_notification(l_batt, r_batt)
-            .show()
-            .unwrap();
+    if let Err(err) = notification.show() {
+        eprintln!("Can't show notification: {}", err);
     }
 }
 
@@ -92,3 +152,63 @@ fn get_bt_sink(handler: &mut SinkController, info: &BudsInfo) -> Option<DeviceIn
         .find(|i| i.proplist.get_str("device.string").unwrap_or_default() == info.inner.address)
         .map(|i| i.to_owned())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLDS: [i8; 3] = [30, 20, 10];
+
+    // Feed the levels in order and collect the notified thresholds
+    fn notify(levels: &[i8]) -> Vec<Option<i8>> {
+        let mut notified = None;
+        levels
+            .iter()
+            .map(|i| crossed_threshold(*i, &THRESHOLDS, &mut notified))
+            .collect()
+    }
+
+    #[test]
+    fn falling_below_threshold() {
+        assert_eq!(
+            notify(&[50, 29, 25, 19, 15, 9]),
+            vec![None, Some(30), None, Some(20), None, Some(10)]
+        );
+    }
+
+    #[test]
+    fn several_thresholds_at_once() {
+        // Only the lowest crossed threshold gets notified
+        assert_eq!(
+            notify(&[50, 15, 12, 5]),
+            vec![None, Some(20), None, Some(10)]
+        );
+        assert_eq!(notify(&[5, 4]), vec![Some(10), None]);
+    }
+
+    #[test]
+    fn rising_level() {
+        // Rising doesn't notify, even when crossing a threshold
+        assert_eq!(notify(&[15, 25, 35, 18]), vec![Some(20), None, None, None]);
+
+        // Once above the highest threshold and the hysteresis, thresholds notify again
+        assert_eq!(notify(&[15, 41, 29]), vec![Some(20), None, Some(30)]);
+    }
+
+    #[test]
+    fn equal_values() {
+        // Reaching a threshold doesn't cross it
+        assert_eq!(notify(&[30, 20, 10]), vec![None, Some(30), Some(20)]);
+        assert_eq!(notify(&[19, 19, 19]), vec![Some(20), None, None]);
+
+        // Exactly at the highest threshold plus the hysteresis doesn't reset
+        assert_eq!(notify(&[25, 40, 25]), vec![Some(30), None, None]);
+    }
+
+    #[test]
+    fn no_thresholds() {
+        let mut notified = None;
+        assert_eq!(crossed_threshold(5, &[], &mut notified), None);
+        assert_eq!(notified, None);
+    }
+}
@@ -1,13 +1,13 @@
-use std::process::exit;
-
+use super::super::super::battery_history::BatterySample;
 use super::super::super::buds_config::{BudsConfig, Config};
 use super::super::super::buds_info::BudsInfo;
+use super::super::super::notification::{self, NotificationEvent};
 use super::super::bt_connection_listener::BudsConnection;
 use super::sink;
 use super::utils;
 
 use async_std::sync::{Arc, Mutex};
-use galaxy_buds_rs::message::status_updated::StatusUpdate;
+use galaxy_buds_rs::message::{bud_property::Placement, status_updated::StatusUpdate};
 
 #[cfg(feature = "pulse-sink")]
 use pulsectl::controllers::SinkController;
@@ -21,21 +21,16 @@ fn update_status(update: &StatusUpdate, info: &mut BudsInfo) {
     info.inner.placement_right = update.placement_right;
 }
 
-// Handle a status update
+// Handle a status update. Returns the battery sample to add to the history, if the
+// battery levels changed. Writing it is left to the caller to not hold any locks
 pub async fn handle(
     update: StatusUpdate,
     info: &mut BudsInfo,
     config: &Arc<Mutex<Config>>,
     connection: &BudsConnection,
-) {
+) -> Option<BatterySample> {
     // Lock the config
-    let mut cfg = config.lock().await;
-
-    // Load the (possibly changed) config values
-    if let Err(err) = cfg.load().await {
-        eprintln!("{}", err);
-        exit(1);
-    }
+    let cfg = config.lock().await;
 
     // Check if current device has a config entry
     if let Some(config) = cfg.get_device_config(&connection.addr) {
@@ -44,9 +39,11 @@ pub async fn handle(
             handle_auto_music(&update, info, &config);
         }
 
-        // handle desktop notification
-        if config.low_battery_notification() {
-            sink::handle_low_battery(&update, info);
+        // handle low battery thresholds and desktop notification
+        sink::handle_low_battery(&update, info, config);
+
+        if got_fully_charged(&update, info) {
+            notification::show(NotificationEvent::FullyCharged, info.inner.model, config);
         }
 
         // Fallback to next available sink if buds
@@ -58,7 +55,30 @@ pub async fn handle(
     }
 
     // Update the local status of the buds
+    let old_sample = BatterySample::from_info(&info.inner);
     update_status(&update, info);
+    info.battery_tracker.update(&mut info.inner);
+
+    // Remember changed battery levels
+    let sample = BatterySample::from_info(&info.inner);
+    if info.inner.ready && sample.differs(&old_sample) {
+        Some(sample)
+    } else {
+        None
+    }
+}
+
+// Returns true if both buds just reached 100% in the case
+fn got_fully_charged(update: &StatusUpdate, info: &BudsInfo) -> bool {
+    let in_case =
+        |placement| placement == Placement::InOpenCase || placement == Placement::InCloseCase;
+
+    info.inner.ready
+        && in_case(update.placement_left)
+        && in_case(update.placement_right)
+        && update.battery_left == 100
+        && update.battery_right == 100
+        && (info.inner.batt_left < 100 || info.inner.batt_right < 100)
 }
 
 /// Handle automatically pausing/playing music on earbuds wearing statu changes
@@ -27,17 +27,14 @@ pub async fn handle(
         // Separate config logic to keep cfg locked as short as possible
         early_exit = {
             // Lock the config
-            let mut cfg = config.lock().await;
-
-            // Load the (possibly changed) config values
-            cfg.load().await.unwrap();
+            let cfg = config.lock().await;
 
             let config = cfg.get_device_config(&connection.addr);
             if config.is_none() {
                 true
             } else {
                 let config = config.unwrap();
-                if !config.hold_to_disconnect.unwrap_or(false) {
+                if !config.hold_to_disconnect() {
                     true
                 } else {
                     false
@@ -59,7 +56,7 @@ pub async fn handle(
     // We don't need that hold count crap if the tap-action is set to 'Disconnect' and touchpads
     // are enabled
     if !info.inner.touchpads_blocked {
-        bluetooth_commands::change_connection_status(&connection.addr, false).await;
+        disconnect(config, connection).await;
         info.reset_last_tp_update();
         return true;
     }
@@ -89,9 +86,20 @@ pub async fn handle(
         && info.right_tp_hold_count >= REQUIRED_TAP_DURATION
     {
         // Disconnect
-        bluetooth_commands::change_connection_status(&connection.addr, false).await;
+        disconnect(config, connection).await;
         info.reset_last_tp_update();
         return true;
     }
     false
 }
+
+// Disconnect the device using the adapter configured for it
+async fn disconnect(config: &Arc<Mutex<Config>>, connection: &BudsConnection) {
+    let adapter = config.lock().await.adapter_for(&connection.addr);
+    if let Err(err) =
+        bluetooth_commands::change_connection_status(&connection.addr, false, adapter.as_deref())
+            .await
+    {
+        eprintln!("Error disconnecting: {}", err);
+    }
+}
@@ -1,6 +1,8 @@
 #![allow(dead_code)]
 
-use galaxy_buds_rs::message::bud_property::Placement;
+use crate::daemon::utils as daemon_utils;
+
+use galaxy_buds_rs::{message::bud_property::Placement, model::Model};
 use mpris::{Player, PlayerFinder};
 use notify_rust::Notification;
 
@@ -36,13 +38,40 @@ pub fn is_wearing_state(left: Placement, right: Placement) -> bool {
     left == Placement::Ear && right == Placement::Ear
 }
 
-pub fn get_desktop_notification(l_batt: i8, r_batt: i8) -> Notification {
+pub fn is_some_in_case_state(left: Placement, right: Placement) -> bool {
+    [left, right]
+        .iter()
+        .any(|i| *i == Placement::InOpenCase || *i == Placement::InCloseCase)
+}
+
+pub fn get_desktop_notification(
+    model: Model,
+    l_batt: i8,
+    r_batt: i8,
+    threshold: i8,
+) -> Notification {
+    let name = daemon_utils::model_name(model);
+    Notification::new()
+        .summary(format!("{} battery low", name).as_str())
+        .body(
+            format!(
+                "The battery of your {} is below {}%: (L: {}%, R: {}%)",
+                name, threshold, l_batt, r_batt
+            )
+            .as_str(),
+        )
+        .icon("battery")
+        .to_owned()
+}
+
+pub fn get_case_notification(model: Model, case_batt: i8, threshold: i8) -> Notification {
+    let name = daemon_utils::model_name(model);
     Notification::new()
-        .summary("Buds Live battery low")
+        .summary(format!("{} case battery low", name).as_str())
         .body(
             format!(
-                "The battery of your Galaxy buds live is pretty low: (L: {}%, R: {}%)",
-                l_batt, r_batt
+                "The battery of your {} case is below {}%: ({}%)",
+                name, threshold, case_batt
             )
             .as_str(),
         )
@@ -3,144 +3,562 @@
  * forwards connection events to the connector
  */
 
+use async_std::{os::unix::net::UnixStream, sync::Mutex, task};
 use bluetooth_serial_port_async::BtSocket;
 use blurz::{
     BluetoothAdapter, BluetoothDevice,
-    BluetoothEvent::{self, Connected},
+    BluetoothEvent::{self, Connected, Powered},
     BluetoothSession,
 };
-use galaxy_buds_rs::model::Model;
 use log::debug;
 
-use std::sync::mpsc::Sender;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Debug, Display};
+use std::sync::{mpsc::Sender, Arc};
 use std::time::Duration;
 
-use super::rfcomm_connector::ConnectionEventData;
+use super::super::buds_config::Config;
+use super::rfcomm_connector::{ConnectionEvent, ConnectionEventData};
+use super::{adapter, model_detection};
 
 /// An active connection to a pair of buds
 #[derive(Debug)]
 pub struct BudsConnection {
     pub addr: String,
-    pub socket: BtSocket,
+    pub socket: Arc<dyn BudsTransport>,
     // pub fd: i32,
 }
 
+/// A bidirectional byte stream to a pair of buds
+pub trait BudsTransport: Debug + Send + Sync {
+    /// Get a stream to read frames from and write frames to
+    fn get_stream(&self) -> UnixStream;
+}
+
+// Real buds connected via RFCOMM
+impl BudsTransport for BtSocket {
+    fn get_stream(&self) -> UnixStream {
+        BtSocket::get_stream(self)
+    }
+}
+
+// One end of a socket pair, used for simulated buds
+impl BudsTransport for UnixStream {
+    fn get_stream(&self) -> UnixStream {
+        self.clone()
+    }
+}
+
+// Time to wait before retrying a failed bluetooth operation
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// An error which occurred while talking to BlueZ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BtError {
+    /// No bluetooth adapter is available
+    AdapterMissing,
+    /// Connecting to the system bus failed
+    Session(String),
+    /// A property of a device couldn't be read
+    DeviceProperty {
+        device: String,
+        property: &'static str,
+        error: String,
+    },
+    /// Any other error reported by BlueZ
+    Other(String),
+}
+
+impl BtError {
+    // Thanks blurz for implementing usable error types
+    pub fn from_adapter_err(err: Box<dyn Error>) -> Self {
+        let s = err.to_string();
+        if s.contains("Bluetooth adapter not found") {
+            BtError::AdapterMissing
+        } else {
+            BtError::Other(s)
+        }
+    }
+}
+
+impl Display for BtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BtError::AdapterMissing => write!(f, "Bluetooth adapter missing"),
+            BtError::Session(err) => write!(f, "Can't connect to the system bus: {}", err),
+            BtError::DeviceProperty {
+                device,
+                property,
+                error,
+            } => write!(f, "Can't read {} of {}: {}", property, device, error),
+            BtError::Other(err) => write!(f, "Bluetooth error: {}", err),
+        }
+    }
+}
+
+/// Provides the information of a bluetooth device required to detect buds
+pub trait DeviceInfoProvider {
+    /// The object path of the device
+    fn id(&self) -> String;
+    fn address(&self) -> Result<String, BtError>;
+    fn name(&self) -> Result<String, BtError>;
+    fn uuids(&self) -> Result<Vec<String>, BtError>;
+}
+
+impl DeviceInfoProvider for BluetoothDevice<'_> {
+    fn id(&self) -> String {
+        self.get_id()
+    }
+
+    fn address(&self) -> Result<String, BtError> {
+        self.get_address()
+            .map_err(|e| property_err(self, "address", e))
+    }
+
+    fn name(&self) -> Result<String, BtError> {
+        self.get_name().map_err(|e| property_err(self, "name", e))
+    }
+
+    fn uuids(&self) -> Result<Vec<String>, BtError> {
+        self.get_uuids().map_err(|e| property_err(self, "uuids", e))
+    }
+}
+
+fn property_err(device: &BluetoothDevice, property: &'static str, err: Box<dyn Error>) -> BtError {
+    BtError::DeviceProperty {
+        device: device.get_id(),
+        property,
+        error: err.to_string(),
+    }
+}
+
 /// Listens for new Bluethooth connections
-pub fn run(sender: Sender<ConnectionEventData>) {
-    let session = &BluetoothSession::create_session(None).unwrap();
-    let mut printed_adapter_missing = false;
+pub fn run(sender: Sender<ConnectionEvent>, config: Arc<Mutex<Config>>) {
+    let session = &create_session();
+
+    // Only print an error once until it changes
+    let mut last_err: Option<BtError> = None;
+
+    // Object paths and addresses of the connected buds
+    let mut connected: HashMap<String, String> = HashMap::new();
 
     'outer: loop {
-        let adapter = BluetoothAdapter::init(session);
-        if let Err(err) = adapter {
-            // Thanks blurz for implementing usable error types
-            let s = err.to_string();
-
-            // On adapter not found, wait and try to connect to it again
-            // maybe the user inserted the adapter later on
-            if s.contains("Bluetooth adapter not found") {
-                std::thread::sleep(Duration::from_secs(2));
-
-                if !printed_adapter_missing {
-                    eprintln!("Bluetooth adapter missing!");
+        // On errors wait and try to init the adapter again,
+        // maybe the user inserted the adapter later on
+        let adapter_name = task::block_on(config.lock()).adapter.clone();
+        let adapter = match adapter::get_adapter(session, adapter_name.as_deref()) {
+            Ok(adapter) => adapter,
+            Err(err) => {
+                if last_err.as_ref() != Some(&err) {
+                    eprintln!("{}", err);
                 }
-                printed_adapter_missing = true;
+                last_err = Some(err);
 
+                std::thread::sleep(RETRY_DELAY);
                 continue;
-            } else {
-                // Every other error should be treated as fatal error
-                println!("Bluetooth error: {}", err);
-                std::process::exit(1);
             }
-        } else {
-            printed_adapter_missing = false;
-        }
+        };
 
-        let adapter = adapter.unwrap();
+        last_err = None;
 
-        // check if a pair of buds is already connected!
-        if let Ok(devices) = adapter.get_device_list() {
-            for device in devices {
-                let device = BluetoothDevice::new(&session, device);
-                let is_connected = device.is_connected();
-                if is_connected.is_err() || !is_connected.unwrap() {
-                    continue;
+        // check if a pair of buds is already connected! Devices can
+        // be configured to use another adapter, so check those too
+        scan_adapter(&sender, session, &adapter, &config, &mut connected);
+        let device_adapters = task::block_on(config.lock()).device_adapters();
+        for name in device_adapters {
+            match adapter::get_adapter(session, Some(&name)) {
+                Ok(other) if other.get_id() != adapter.get_id() => {
+                    scan_adapter(&sender, session, &other, &config, &mut connected);
                 }
-
-                check_device(&sender, &session, device.get_id());
+                Ok(_) => (),
+                Err(err) => eprintln!("Can't use adapter {}: {}", name, err),
             }
         }
 
         // Handle all future connection events
         loop {
             if adapter.is_powered().is_err() {
+                // The adapter is gone, so are its devices
+                disconnect_adapter(&sender, &mut connected, &adapter.get_id());
                 continue 'outer;
             }
 
             for event in session.incoming(10000000).map(BluetoothEvent::from) {
-                if event.is_none() {
-                    continue;
-                }
+                match event {
+                    Some(Connected {
+                        object_path,
+                        connected: true,
+                    }) => {
+                        println!("device {:#?}", object_path);
+                        let path = object_path.clone();
+                        if let Some(address) = check_device(&sender, session, path, &config) {
+                            connected.insert(object_path, address);
+                        }
+                    }
+
+                    // Also sent before a device gets removed
+                    Some(Connected {
+                        object_path,
+                        connected: false,
+                    }) => {
+                        disconnect_device(&sender, &mut connected, &object_path);
+                    }
 
-                if let Connected {
-                    object_path,
-                    connected,
-                } = event.unwrap()
-                {
-                    if !connected {
-                        continue;
+                    Some(Powered {
+                        object_path,
+                        powered: false,
+                    }) => {
+                        disconnect_adapter(&sender, &mut connected, &object_path);
                     }
 
-                    println!("device {:#?}", object_path);
-                    check_device(&sender, &session, object_path);
+                    _ => (),
+                }
+            }
+        }
+    }
+}
+
+// Check the devices which are already connected to an adapter
+fn scan_adapter(
+    sender: &Sender<ConnectionEvent>,
+    session: &BluetoothSession,
+    adapter: &BluetoothAdapter,
+    config: &Arc<Mutex<Config>>,
+    connected: &mut HashMap<String, String>,
+) {
+    let devices = match adapter.get_device_list() {
+        Ok(devices) => devices,
+        Err(_) => return,
+    };
+
+    for device in devices {
+        let device = BluetoothDevice::new(session, device);
+        let is_connected = device.is_connected();
+        if is_connected.is_err() || !is_connected.unwrap() {
+            continue;
+        }
+
+        let path = device.get_id();
+        if connected.contains_key(&path) {
+            continue;
+        }
+
+        if let Some(address) = check_device(sender, session, path.clone(), config) {
+            connected.insert(path, address);
+        }
+    }
+}
+
+// Let the connection handler know that a device got disconnected
+fn disconnect_device(
+    sender: &Sender<ConnectionEvent>,
+    connected: &mut HashMap<String, String>,
+    device_path: &str,
+) {
+    if let Some(address) = connected.remove(device_path) {
+        sender
+            .send(ConnectionEvent::Disconnected { address })
+            .unwrap();
+    }
+}
+
+// Let the connection handler know that all devices of an adapter got disconnected
+fn disconnect_adapter(
+    sender: &Sender<ConnectionEvent>,
+    connected: &mut HashMap<String, String>,
+    adapter_path: &str,
+) {
+    connected.retain(|path, address| {
+        if !adapter::is_on_adapter(path, adapter_path) {
+            return true;
+        }
+
+        sender
+            .send(ConnectionEvent::Disconnected {
+                address: address.clone(),
+            })
+            .unwrap();
+        false
+    });
+}
+
+// Connect to the system bus. Retries until it succeeds
+fn create_session() -> BluetoothSession {
+    let mut printed_err = false;
+
+    loop {
+        match BluetoothSession::create_session(None) {
+            Ok(session) => return session,
+            Err(err) => {
+                if !printed_err {
+                    eprintln!("{}", BtError::Session(err.to_string()));
+                    printed_err = true;
                 }
+                std::thread::sleep(RETRY_DELAY);
             }
         }
     }
 }
 
-// We need this behaivor twice
-fn check_device(sender: &Sender<ConnectionEventData>, session: &BluetoothSession, device: String) {
+// We need this behaivor twice. Returns the address of supported devices
+fn check_device(
+    sender: &Sender<ConnectionEvent>,
+    session: &BluetoothSession,
+    device: String,
+    config: &Arc<Mutex<Config>>,
+) -> Option<String> {
     let device = BluetoothDevice::new(session, device);
 
-    if !supported_device(&device) {
-        let name = device.get_name().unwrap();
-        debug!("Not supported: {name}");
-        return;
+    // Skip devices which can't be inspected
+    let event = match device_event(&device) {
+        Ok(Some(event)) => event,
+        Ok(None) => return None,
+        Err(err) => {
+            eprintln!("Skipping device: {}", err);
+            return None;
+        }
+    };
+
+    // Skip devices connected via another adapter than the configured one
+    let adapter_name = task::block_on(config.lock()).adapter_for(&event.address);
+    if let Some(adapter_name) = adapter_name {
+        match adapter::get_adapter(session, Some(&adapter_name)) {
+            Ok(adapter) if adapter::has_device(&adapter, &device.get_id()) => (),
+            _ => {
+                debug!("Not using adapter {}: {}", adapter_name, event.address);
+                return None;
+            }
+        }
+    }
+
+    let address = event.address.clone();
+    sender.send(ConnectionEvent::Connected(event)).unwrap();
+    Some(address)
+}
+
+/// Get the connection event for a device. Returns None if the device isn't supported
+pub fn device_event<D: DeviceInfoProvider>(
+    device: &D,
+) -> Result<Option<ConnectionEventData>, BtError> {
+    // Devices don't need to have a name
+    let name = device.name().ok();
+    let uuids = device.uuids()?;
+
+    if !model_detection::supported_device(&uuids) {
+        debug!("Not supported: {}", name.as_deref().unwrap_or(&device.id()));
+        return Ok(None);
     }
 
-    sender
-        .send(ConnectionEventData {
-            address: device.get_address().unwrap(),
-            model: name_to_model(device.get_name().unwrap().as_str()),
-        })
-        .unwrap();
+    Ok(Some(ConnectionEventData {
+        address: device.address()?,
+        model: model_detection::detect_model(&uuids, name.as_deref()),
+        name,
+        transport: None,
+    }))
+}
+
+/// Get the address of an adapter given by its name or address
+pub fn get_adapter_address(adapter: &str) -> Result<String, BtError> {
+    let session =
+        BluetoothSession::create_session(None).map_err(|e| BtError::Session(e.to_string()))?;
+
+    adapter::get_adapter(&session, Some(adapter))?
+        .get_address()
+        .map_err(|e| BtError::Other(e.to_string()))
+}
+
+/// Checks whether a device is connected at the bluetooth level via the given adapter
+pub fn is_device_connected(address: &str, adapter: Option<&str>) -> bool {
+    let session = match BluetoothSession::create_session(None) {
+        Ok(session) => session,
+        Err(_) => return false,
+    };
+
+    let devices = match adapter::get_adapter(&session, adapter).map(|i| i.get_device_list()) {
+        Ok(Ok(devices)) => devices,
+        _ => return false,
+    };
+
+    devices
+        .into_iter()
+        .map(|i| BluetoothDevice::new(&session, i))
+        .find(|i| i.get_address().map(|i| i == address).unwrap_or(false))
+        .map(|i| i.is_connected().unwrap_or(false))
+        .unwrap_or(false)
 }
 
-/// Checks whether a device is a pair of buds live
-pub fn supported_device(device: &BluetoothDevice) -> bool {
-    device
-        .get_uuids()
-        .unwrap()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use galaxy_buds_rs::model::Model;
+    use std::sync::mpsc;
+
+    const SPP_UUID: &str = "00001101-0000-1000-8000-00805f9b34fb";
+    const SAMSUNG_SPP_UUID: &str = "2e73a4ad-332d-41fc-90e2-16bef06523f2";
+
+    // A device whose properties can fail to be read
+    struct FakeDevice {
+        path: String,
+        address: Result<String, BtError>,
+        name: Result<String, BtError>,
+        uuids: Result<Vec<String>, BtError>,
+    }
+
+    impl FakeDevice {
+        fn buds(name: &str) -> Self {
+            Self {
+                path: "/org/bluez/hci0/dev_00_00_00_00_00_01".to_owned(),
+                address: Ok("00:00:00:00:00:01".to_owned()),
+                name: Ok(name.to_owned()),
+                uuids: Ok(vec![SPP_UUID.to_owned(), SAMSUNG_SPP_UUID.to_owned()]),
+            }
+        }
+
+        // The error BlueZ returns for properties of a device that got removed
+        fn err(&self, property: &'static str) -> BtError {
+            BtError::DeviceProperty {
+                device: self.path.clone(),
+                property,
+                error: "org.freedesktop.DBus.Error.UnknownObject".to_owned(),
+            }
+        }
+
+        // A device which disappeared while getting inspected
+        fn disappeared(mut self) -> Self {
+            self.address = Err(self.err("address"));
+            self.name = Err(self.err("name"));
+            self.uuids = Err(self.err("uuids"));
+            self
+        }
+    }
+
+    impl DeviceInfoProvider for FakeDevice {
+        fn id(&self) -> String {
+            self.path.clone()
+        }
+
+        fn address(&self) -> Result<String, BtError> {
+            self.address.clone()
+        }
+
+        fn name(&self) -> Result<String, BtError> {
+            self.name.clone()
+        }
+
+        fn uuids(&self) -> Result<Vec<String>, BtError> {
+            self.uuids.clone()
+        }
+    }
+
+    fn disconnected(rx: &mpsc::Receiver<ConnectionEvent>) -> Vec<String> {
+        rx.try_iter()
+            .filter_map(|i| match i {
+                ConnectionEvent::Disconnected { address } => Some(address),
+                ConnectionEvent::Connected(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detects_buds() {
+        let event = device_event(&FakeDevice::buds("Galaxy Buds Live"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.address, "00:00:00:00:00:01");
+        assert_eq!(event.name.as_deref(), Some("Galaxy Buds Live"));
+        assert_eq!(event.model, Model::BudsLive);
+    }
+
+    #[test]
+    fn skips_unsupported_devices() {
+        let mut device = FakeDevice::buds("Keyboard");
+        device.uuids = Ok(vec![]);
+        device.address = Err(device.err("address"));
+        assert_eq!(device_event(&device).unwrap().map(|i| i.address), None);
+    }
+
+    #[test]
+    fn optional_property_errors() {
+        // The model gets detected from the services if the name is missing
+        let mut device = FakeDevice::buds("");
+        device.name = Err(device.err("name"));
+        let event = device_event(&device).unwrap().unwrap();
+        assert_eq!(event.name, None);
+        assert_eq!(event.model, Model::BudsPlus);
+    }
+
+    #[test]
+    fn required_property_errors() {
+        let mut device = FakeDevice::buds("Galaxy Buds Live");
+        device.uuids = Err(device.err("uuids"));
+        let err = device_event(&device).map(|_| ()).unwrap_err();
+        assert_eq!(err, device.err("uuids"));
+
+        let mut device = FakeDevice::buds("Galaxy Buds Live");
+        device.address = Err(device.err("address"));
+        let err = device_event(&device).map(|_| ()).unwrap_err();
+        assert_eq!(err, device.err("address"));
+    }
+
+    #[test]
+    fn disappeared_device() {
+        let device = FakeDevice::buds("Galaxy Buds Live").disappeared();
+        let err = device_event(&device).map(|_| ()).unwrap_err();
+        assert!(matches!(err, BtError::DeviceProperty { .. }), "{}", err);
+
+        // A disconnect of a device which never got connected is ignored
+        let (tx, rx) = mpsc::channel();
+        let mut connected = HashMap::new();
+        disconnect_device(&tx, &mut connected, &device.path);
+        assert!(disconnected(&rx).is_empty());
+    }
+
+    #[test]
+    fn disconnected_device() {
+        let (tx, rx) = mpsc::channel();
+        let mut connected: HashMap<String, String> = [
+            ("/org/bluez/hci0/dev_1", "00:00:00:00:00:01"),
+            ("/org/bluez/hci0/dev_2", "00:00:00:00:00:02"),
+        ]
+        .iter()
+        .map(|(path, address)| (path.to_string(), address.to_string()))
+        .collect();
+
+        disconnect_device(&tx, &mut connected, "/org/bluez/hci0/dev_1");
+        disconnect_device(&tx, &mut connected, "/org/bluez/hci0/dev_1");
+        assert_eq!(disconnected(&rx), vec!["00:00:00:00:00:01"]);
+        assert_eq!(connected.len(), 1);
+    }
+
+    #[test]
+    fn adapter_gone() {
+        let (tx, rx) = mpsc::channel();
+        let mut connected: HashMap<String, String> = [
+            ("/org/bluez/hci0/dev_1", "00:00:00:00:00:01"),
+            ("/org/bluez/hci1/dev_2", "00:00:00:00:00:02"),
+            ("/org/bluez/hci10/dev_3", "00:00:00:00:00:03"),
+        ]
         .iter()
-        .any(|s| s.to_lowercase() == "00001101-0000-1000-8000-00805f9b34fb")
-}
-
-/// Gives devices model from its name
-fn name_to_model(device_name: &str) -> Model {
-    let device_name = device_name.to_lowercase();
-
-    if device_name.contains("buds live") {
-        Model::BudsLive
-    } else if device_name.contains("buds pro") {
-        Model::BudsPro
-    } else if device_name.contains("buds 2 pro") {
-        Model::BudsPro2
-    } else if device_name.contains("buds+") {
-        Model::BudsPlus
-    } else if device_name.contains("buds2") {
-        Model::Buds2
-    } else {
-        Model::Buds
+        .map(|(path, address)| (path.to_string(), address.to_string()))
+        .collect();
+
+        // Only the devices of the removed adapter get disconnected
+        disconnect_adapter(&tx, &mut connected, "/org/bluez/hci1");
+        assert_eq!(disconnected(&rx), vec!["00:00:00:00:00:02"]);
+        assert_eq!(connected.len(), 2);
+
+        disconnect_adapter(&tx, &mut connected, "/org/bluez/hci1");
+        assert!(disconnected(&rx).is_empty());
+    }
+
+    #[test]
+    fn adapter_errors() {
+        let err = BtError::from_adapter_err("Bluetooth adapter not found".into());
+        assert_eq!(err, BtError::AdapterMissing);
+
+        let err = BtError::from_adapter_err("org.bluez.Error.NotReady".into());
+        assert_eq!(err, BtError::Other("org.bluez.Error.NotReady".to_owned()));
     }
 }
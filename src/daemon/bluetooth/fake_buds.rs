@@ -0,0 +1,221 @@
+/*
+ * Simulates a pair of buds by sending scripted frames over a socket pair.
+ * Allows running the daemon without bluetooth hardware.
+ *
+ * Scripts are toml files like:
+ *
+ *   address = "00:00:00:00:00:01"
+ *   model = "buds-live"
+ *
+ *   [[frames]]
+ *   delay_ms = 500
+ *   id = "extended_status_updated"
+ *   payload = "0a 01 64 5a ..."
+ *
+ * The payload is the raw (hex encoded) payload of the message, as sent by the buds.
+ * Frames get sent in order. After the last frame the connection stays open.
+ *
+ * Captures recorded by the daemon can be replayed the same way
+ */
+
+use super::super::capture::{self, Direction};
+use super::super::utils::parse_model;
+use super::bt_connection_listener::BudsTransport;
+use super::rfcomm_connector::ConnectionEventData;
+
+use async_std::{io::prelude::*, os::unix::net::UnixStream, task};
+use galaxy_buds_rs::{message::ids, model::Model};
+use serde::Deserialize;
+
+use std::{sync::Arc, time::Duration};
+
+/// Frames to send along with the delay (in ms) before sending them
+type Frames = Vec<(u64, Vec<u8>)>;
+
+/// A scripted pair of simulated buds
+#[derive(Debug, Clone, Deserialize)]
+pub struct FakeBudsScript {
+    pub address: String,
+    pub model: String,
+    #[serde(default)]
+    pub frames: Vec<FakeFrame>,
+}
+
+/// A single frame sent by simulated buds
+#[derive(Debug, Clone, Deserialize)]
+pub struct FakeFrame {
+    /// Time to wait before sending the frame
+    #[serde(default)]
+    pub delay_ms: u64,
+    /// Name or number of the message id
+    pub id: String,
+    #[serde(default)]
+    pub payload: String,
+}
+
+impl FakeBudsScript {
+    /// Read a script from a file
+    pub async fn load(path: &str) -> Result<Self, String> {
+        let content = async_std::fs::read_to_string(path)
+            .await
+            .map_err(|e| e.to_string())?;
+        toml::from_str(&content).map_err(|e| e.to_string())
+    }
+}
+
+/// Start simulating the buds of a script. Returns the connection
+/// event which makes the daemon connect to the simulated buds
+pub fn from_script(script: FakeBudsScript) -> Result<ConnectionEventData, String> {
+    let model = parse_model(&script.model)?;
+
+    // Encode all frames before starting to catch errors early
+    let mut frames = Vec::with_capacity(script.frames.len());
+    for frame in &script.frames {
+        let id = parse_id(&frame.id)?;
+        let payload = parse_hex(&frame.payload)?;
+        frames.push((frame.delay_ms, encode_frame(id, &payload, model)));
+    }
+
+    connect(script.address, model, frames)
+}
+
+/// Start simulating every device of a capture file by sending the
+/// recorded data with the recorded timing
+pub fn from_capture(path: &str) -> Result<Vec<ConnectionEventData>, String> {
+    let entries = capture::read_capture(path)?;
+
+    // Group the received data by device
+    let mut devices: Vec<(String, Model, Frames)> = vec![];
+    let mut last_timestamps: Vec<u64> = vec![];
+    for entry in entries.iter().filter(|i| i.direction == Direction::In) {
+        let pos = match devices.iter().position(|i| i.0 == entry.address) {
+            Some(pos) => pos,
+            None => {
+                devices.push((entry.address.clone(), entry.model, vec![]));
+                last_timestamps.push(entry.timestamp);
+                devices.len() - 1
+            }
+        };
+
+        let delay = entry.timestamp.saturating_sub(last_timestamps[pos]);
+        last_timestamps[pos] = entry.timestamp;
+        devices[pos].2.push((delay, entry.bytes()?));
+    }
+
+    devices
+        .into_iter()
+        .map(|(address, model, frames)| {
+            println!("Replaying {} frames of {}", frames.len(), address);
+            connect(address, model, frames)
+        })
+        .collect()
+}
+
+// Spawn a task sending the frames and return the
+// connection event for the other end of the connection
+fn connect(address: String, model: Model, frames: Frames) -> Result<ConnectionEventData, String> {
+    let (daemon_end, buds_end) = UnixStream::pair().map_err(|e| e.to_string())?;
+    task::spawn(run(buds_end, frames));
+
+    let transport: Arc<dyn BudsTransport> = Arc::new(daemon_end);
+    Ok(ConnectionEventData {
+        address,
+        name: None,
+        model,
+        transport: Some(transport),
+    })
+}
+
+// Send all frames and keep the connection open until the daemon closes it
+async fn run(mut stream: UnixStream, frames: Frames) {
+    // Drop everything the daemon sends to the buds
+    let mut reader = stream.clone();
+    let drain = task::spawn(async move {
+        let mut buff = [0u8; 1024];
+        while let Ok(n) = reader.read(&mut buff).await {
+            if n == 0 {
+                break;
+            }
+        }
+    });
+
+    for (delay, frame) in frames {
+        task::sleep(Duration::from_millis(delay)).await;
+        if let Err(err) = stream.write_all(&frame).await {
+            eprintln!("Fake buds: can't send frame: {}", err);
+            return;
+        }
+    }
+
+    drain.await;
+}
+
+/// Build a frame as it would be sent by the buds
+pub fn encode_frame(id: u8, payload: &[u8], model: Model) -> Vec<u8> {
+    // Size of the id, the payload and the crc
+    let size = payload.len() + 3;
+
+    let mut frame = Vec::with_capacity(size + 4);
+    if model == Model::Buds {
+        frame.extend_from_slice(&[0xFE, 0x00, size as u8]);
+    } else {
+        frame.extend_from_slice(&[0xFD, (size & 0xFF) as u8, ((size >> 8) & 0x03) as u8]);
+    }
+
+    let start = frame.len();
+    frame.push(id);
+    frame.extend_from_slice(payload);
+
+    let crc = crc16(&frame[start..]);
+    frame.push((crc & 0xFF) as u8);
+    frame.push((crc >> 8) as u8);
+
+    frame.push(if model == Model::Buds { 0xEE } else { 0xDD });
+    frame
+}
+
+// CRC16 CCITT (XMODEM) used by the buds
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+fn parse_id(s: &str) -> Result<u8, String> {
+    Ok(match s.to_lowercase().as_str() {
+        "status_updated" => ids::STATUS_UPDATED,
+        "extended_status_updated" => ids::EXTENDED_STATUS_UPDATED,
+        "touchpad_action" => ids::TOUCHPAD_ACTION,
+        "debug_get_all_data" => ids::DEBUG_GET_ALL_DATA,
+        "ambient_mode_updated" => ids::AMBIENT_MODE_UPDATED,
+        "noise_reduction_mode_update" => ids::NOISE_REDUCTION_MODE_UPDATE,
+        "usage_report" => ids::USAGE_REPORT,
+        _ => s.parse::<u8>().map_err(|_| format!("Unknown id: {}", s))?,
+    })
+}
+
+// Parse hex bytes. Whitespaces and colons between the bytes are ignored
+fn parse_hex(s: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<char> = s
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ':')
+        .collect();
+
+    digits
+        .chunks(2)
+        .map(|byte| match byte {
+            [high, low] => Some((high.to_digit(16)? * 16 + low.to_digit(16)?) as u8),
+            _ => None,
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| format!("Invalid payload: {}", s))
+}
@@ -1,3 +1,6 @@
+pub mod adapter;
 mod bean_connection;
 pub mod bt_connection_listener;
+pub mod fake_buds;
+mod model_detection;
 pub mod rfcomm_connector;
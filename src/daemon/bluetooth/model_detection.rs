@@ -0,0 +1,122 @@
+/*
+ * Detects the model of a pair of buds from the
+ * metadata BlueZ provides about the device. The PnP ID
+ * isn't used since there is no reliable list of the
+ * product ids of the buds
+ */
+
+use galaxy_buds_rs::model::Model;
+
+// Serial port service, provided by all buds
+const SPP_UUID: &str = "00001101-0000-1000-8000-00805f9b34fb";
+
+// Serial port service only provided by the first Galaxy Buds
+const LEGACY_BUDS_UUID: &str = "00001102-0000-1000-8000-00805f9b34fd";
+
+// Samsungs serial port service, provided by all buds newer than the first Galaxy Buds
+const SAMSUNG_SPP_UUID: &str = "2e73a4ad-332d-41fc-90e2-16bef06523f2";
+
+/// Checks whether a device with the given services is a pair of buds
+pub fn supported_device(uuids: &[String]) -> bool {
+    has_uuid(uuids, SPP_UUID)
+}
+
+/// Detect the model of a device. The services tell the first Galaxy Buds apart from newer
+/// models. The exact model of newer buds is detected from their name. If the user changed
+/// the name, the model can be set in the config
+pub fn detect_model(uuids: &[String], name: Option<&str>) -> Model {
+    if has_uuid(uuids, LEGACY_BUDS_UUID) {
+        return Model::Buds;
+    }
+
+    let newer = has_uuid(uuids, SAMSUNG_SPP_UUID);
+
+    // Only the first Galaxy Buds are called just "buds"
+    let name_model = name
+        .and_then(name_to_model)
+        .filter(|i| !newer || *i != Model::Buds);
+
+    name_model.unwrap_or(if newer { Model::BudsPlus } else { Model::Buds })
+}
+
+fn has_uuid(uuids: &[String], uuid: &str) -> bool {
+    uuids.iter().any(|s| s.eq_ignore_ascii_case(uuid))
+}
+
+/// Gives devices model from its name
+fn name_to_model(device_name: &str) -> Option<Model> {
+    let device_name = device_name.to_lowercase();
+
+    // More specific names have to be checked first
+    Some(
+        if device_name.contains("buds2 pro") || device_name.contains("buds 2 pro") {
+            Model::BudsPro2
+        } else if device_name.contains("buds pro") {
+            Model::BudsPro
+        } else if device_name.contains("buds live") {
+            Model::BudsLive
+        } else if device_name.contains("buds+") || device_name.contains("buds plus") {
+            Model::BudsPlus
+        } else if device_name.contains("buds2") || device_name.contains("buds 2") {
+            Model::Buds2
+        } else if device_name.contains("buds") {
+            Model::Buds
+        } else {
+            return None;
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Services, name and the expected model
+    type Case<'a> = (&'a [String], Option<&'a str>, Model);
+
+    fn uuids(uuids: &[&str]) -> Vec<String> {
+        uuids.iter().map(|i| i.to_string()).collect()
+    }
+
+    #[test]
+    fn detect() {
+        let legacy = uuids(&[SPP_UUID, LEGACY_BUDS_UUID]);
+        let newer = uuids(&[SPP_UUID, SAMSUNG_SPP_UUID]);
+        let spp = uuids(&[SPP_UUID]);
+
+        let cases: Vec<Case> = vec![
+            // Services
+            (&legacy, Some("Galaxy Buds (1A2B)"), Model::Buds),
+            (&legacy, Some("Galaxy Buds Pro"), Model::Buds),
+            (&legacy, None, Model::Buds),
+            // Factory names
+            (&newer, Some("Galaxy Buds+ (1A2B)"), Model::BudsPlus),
+            (&newer, Some("Galaxy Buds Live (1A2B)"), Model::BudsLive),
+            (&newer, Some("Galaxy Buds Pro (1A2B)"), Model::BudsPro),
+            (&newer, Some("Galaxy Buds2 (1A2B)"), Model::Buds2),
+            (&newer, Some("Galaxy Buds2 Pro (1A2B)"), Model::BudsPro2),
+            (&spp, Some("Galaxy Buds 2 Pro"), Model::BudsPro2),
+            (&spp, Some("galaxy buds plus"), Model::BudsPlus),
+            (&spp, Some("Galaxy Buds (1A2B)"), Model::Buds),
+            // Renamed buds fall back to the services
+            (&newer, Some("My headphones"), Model::BudsPlus),
+            (&newer, Some("Office buds"), Model::BudsPlus),
+            (&newer, None, Model::BudsPlus),
+            (&spp, Some("Headphones"), Model::Buds),
+            (&spp, None, Model::Buds),
+        ];
+
+        for (uuids, name, expected) in cases {
+            assert_eq!(detect_model(uuids, name), expected, "name: {:?}", name);
+        }
+    }
+
+    #[test]
+    fn supported() {
+        assert!(supported_device(&uuids(&[
+            "00001101-0000-1000-8000-00805F9B34FB"
+        ])));
+        assert!(!supported_device(&uuids(&[SAMSUNG_SPP_UUID])));
+        assert!(!supported_device(&[]));
+    }
+}
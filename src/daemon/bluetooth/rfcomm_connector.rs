@@ -1,31 +1,50 @@
 use super::super::buds_config::{BudsConfig, Config};
-use super::super::buds_info::BudsInfo;
-use super::bean_connection;
-use super::bt_connection_listener::BudsConnection;
+use super::super::buds_event::{BudsEvent, EventSubscribers};
+use super::super::buds_info::{BudsInfo, LinkState};
+use super::super::capture::Recorder;
+use super::super::notification::{self, NotificationEvent};
+use super::bean_connection::{self, listener::ListenerExit};
+use super::bt_connection_listener::{self, BudsConnection, BudsTransport};
 
 use async_std::sync::Arc;
 use async_std::sync::Mutex;
+use async_std::task;
 use bluetooth_serial_port_async::{BtAddr, BtProtocol, BtSocket};
 use galaxy_buds_rs::model::Model;
 use log::info;
 
+use nix::libc;
+
 use std::collections::HashMap;
+use std::net::Shutdown;
+use std::os::unix::io::AsRawFd;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+// Delay before the first reconnect attempt. Doubled after each failed attempt
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
 
 /// The connection handler keeps track of
 /// all connected devices and its status
 pub struct ConnHandler {
     connected_devices: Vec<String>,
+    // Set once a device got removed to stop its listener task
+    stop_flags: HashMap<String, Arc<AtomicBool>>,
     pub connection_data: Arc<Mutex<ConnectionData>>,
+    config: Arc<Mutex<Config>>,
 }
 
 impl ConnHandler {
     /// Create a new Connection handler
-    pub fn new(cd: Arc<Mutex<ConnectionData>>) -> Self {
+    pub fn new(cd: Arc<Mutex<ConnectionData>>, config: Arc<Mutex<Config>>) -> Self {
         ConnHandler {
             connected_devices: Vec::new(),
+            stop_flags: HashMap::new(),
             connection_data: cd,
+            config,
         }
     }
 
@@ -34,14 +53,43 @@ impl ConnHandler {
         self.connected_devices.iter().any(|i| **i == *dev)
     }
 
-    /// Add a device to the ConnHandler
-    pub fn add_device(&mut self, dev: String) {
+    /// Add a device to the ConnHandler. Returns a flag
+    /// which gets set once the device got removed
+    pub fn add_device(&mut self, dev: String) -> Arc<AtomicBool> {
+        let stop = Arc::new(AtomicBool::new(false));
+        self.stop_flags.insert(dev.clone(), Arc::clone(&stop));
         self.connected_devices.push(dev);
+        stop
     }
 
     /// Remove a device from the ConnHandler
     pub async fn remove_device(&mut self, dev: &str) {
-        self.connection_data.lock().await.data.remove(dev);
+        let removed = {
+            let mut connection_data = self.connection_data.lock().await;
+            let removed = connection_data.data.remove(dev);
+
+            if self.has_device(dev) {
+                connection_data
+                    .subscribers
+                    .publish(BudsEvent::Disconnected {
+                        address: dev.to_owned(),
+                    });
+            }
+
+            removed
+        };
+
+        if let Some(stop) = self.stop_flags.remove(dev) {
+            stop.store(true, Ordering::SeqCst);
+        }
+
+        if let Some(info) = removed {
+            // Make the listener task stop reading
+            let _ = info.stream.shutdown(Shutdown::Both);
+
+            let event = NotificationEvent::Disconnected;
+            notification::notify(event, dev, info.inner.model, &self.config).await;
+        }
 
         let pos = self.get_item_pos(dev);
         if pos.is_none() {
@@ -51,6 +99,20 @@ impl ConnHandler {
         self.connected_devices.remove(pos.unwrap());
     }
 
+    /// Update the state of the RFCOMM link of a device
+    pub async fn set_link_state(&self, dev: &str, state: LinkState) {
+        let mut lock = self.connection_data.lock().await;
+        let connection_data = &mut *lock;
+
+        if let Some(info) = connection_data.data.get_mut(dev) {
+            let old_state = info.inner.clone();
+            info.inner.link_state = state;
+            connection_data
+                .subscribers
+                .publish_changes(&old_state, &info.inner);
+        }
+    }
+
     /// Get the position of a device in the ConnHandler device vector
     pub fn get_item_pos(&self, dev: &str) -> Option<usize> {
         for (i, v) in self.connected_devices.iter().enumerate() {
@@ -65,39 +127,25 @@ impl ConnHandler {
 /// Shared data for informations about connected buds
 pub struct ConnectionData {
     pub data: HashMap<String, BudsInfo>,
+    pub subscribers: EventSubscribers,
 }
 
 impl ConnectionData {
     pub fn new() -> Self {
         ConnectionData {
             data: HashMap::new(),
+            subscribers: EventSubscribers::default(),
         }
     }
 
-    /// Returns a device by its address. If no address is set,
-    /// the first device gets returned
+    /// Returns a device by its address
     pub fn get_device(&self, addr: &str) -> Option<&BudsInfo> {
-        if addr.is_empty() {
-            return self.get_first_device();
-        }
-
-        for v in self.data.values() {
-            if v.inner.address == *addr {
-                return Some(v);
-            }
-        }
-
-        None
+        self.data.values().find(|v| v.inner.address == *addr)
     }
 
     /// Get device mutable
     pub fn get_device_mut(&mut self, addr: &str) -> Option<&mut BudsInfo> {
-        for (_, v) in self.data.iter_mut() {
-            if v.inner.address == *addr {
-                return Some(v);
-            }
-        }
-        None
+        self.data.values_mut().find(|v| v.inner.address == *addr)
     }
 
     // Get the full address of a device
@@ -106,51 +154,81 @@ impl ConnectionData {
         addr: &str,
         config: &Arc<Mutex<Config>>,
     ) -> Option<String> {
-        if addr.is_empty() {
-            if self.get_device_count() == 0 {
-                if let Some(dev) = config.lock().await.get_default_device() {
-                    return Some(dev.address.clone());
-                } else {
-                    return None;
-                }
-            }
-            return self.get_first_device().map(|i| i.inner.address.clone());
+        let default = config
+            .lock()
+            .await
+            .get_default_device()
+            .map(|i| i.address.clone());
+
+        self.select_device(addr, default.as_deref())
+    }
+
+    /// Select the device a command gets executed on. If no address is given, the
+    /// default device is used if it's connected, otherwise the most recently used
+    /// device. If no device is connected, the default device gets returned
+    pub fn select_device(&self, addr: &str, default: Option<&str>) -> Option<String> {
+        if !addr.is_empty() {
+            return self
+                .get_device(addr)
+                .filter(|i| i.inner.ready)
+                .map(|i| i.inner.address.clone());
         }
 
-        let device = self.get_device(addr)?;
+        let ready: Vec<&BudsInfo> = self.data.values().filter(|i| i.inner.ready).collect();
+        if ready.is_empty() {
+            return default.map(|i| i.to_owned());
+        }
 
-        if !device.inner.ready {
-            return None;
+        if let Some(default) = default {
+            if ready.iter().any(|i| i.inner.address == default) {
+                return Some(default.to_owned());
+            }
         }
 
-        Some(device.inner.address.clone())
+        ready
+            .iter()
+            .max_by_key(|i| i.last_used)
+            .map(|i| i.inner.address.clone())
     }
 
     /// Get count of connected devices
     pub fn get_device_count(&self) -> usize {
-        self.data
-            .iter()
-            .find(|(_, item)| item.inner.ready)
-            .iter()
-            .count()
-    }
-
-    fn get_first_device(&self) -> Option<&BudsInfo> {
-        self.data.iter().next().map(|(_, v)| v)
+        self.data.values().filter(|i| i.inner.ready).count()
     }
 }
 
 /// run the connection handler
 pub async fn run(
-    rec: Receiver<ConnectionEventData>,
+    rec: Receiver<ConnectionEvent>,
     cd: Arc<Mutex<ConnectionData>>,
     config: Arc<Mutex<Config>>,
+    recorder: Option<Arc<Recorder>>,
 ) {
-    let connection_handler = ConnHandler::new(cd);
+    let connection_handler = ConnHandler::new(cd, Arc::clone(&config));
     let arc_ch = Arc::new(Mutex::new(connection_handler));
 
-    for i in rec {
-        let connection = {
+    for event in rec {
+        let mut i = match event {
+            ConnectionEvent::Connected(i) => i,
+            ConnectionEvent::Disconnected { address } => {
+                let mut connection_handler = arc_ch.lock().await;
+                if connection_handler.has_device(&address) {
+                    println!("Device {} disconnected", address);
+                    connection_handler.remove_device(&address).await;
+                }
+                continue;
+            }
+        };
+
+        // The detected model can be overridden in the config
+        if i.transport.is_none() {
+            let cfg = config.lock().await;
+            if let Some(model) = cfg.get_device_config(&i.address).and_then(|i| i.model()) {
+                i.model = model;
+            }
+        }
+
+        let (connection, stop) = {
             let mut connection_handler = arc_ch.lock().await;
 
             // Ignore already connected devices
@@ -158,57 +236,379 @@ pub async fn run(
                 continue;
             }
 
-            // Connect to the RFCOMM interface of the buds
-            let connection = connect_rfcomm(i.address.clone());
+            // Connect to the RFCOMM interface of the buds, unless
+            // the event already comes with an established connection
+            let connection = match i.transport {
+                Some(ref transport) => Ok(BudsConnection {
+                    addr: i.address.clone(),
+                    socket: Arc::clone(transport),
+                }),
+                None => {
+                    let adapter = config.lock().await.adapter_for(&i.address);
+                    connect_rfcomm(i.address.clone(), adapter.as_deref())
+                }
+            };
             if let Err(err) = connection {
                 eprintln!("Error connecting to rfcomm: {:?}", err);
                 continue;
             }
 
             // Add device to the connection handler
-            connection_handler.add_device(i.address.to_owned());
+            let stop = connection_handler.add_device(i.address.to_owned());
 
             info!("Connected successfully to {}", i.model);
 
-            connection
+            connection_handler
+                .connection_data
+                .lock()
+                .await
+                .subscribers
+                .publish(BudsEvent::Connected {
+                    address: i.address.clone(),
+                });
+
+            (connection, stop)
         };
 
         // Set default config for (apparently) new device
         {
             let mut cfg = config.lock().await;
             if !cfg.has_device_config(&i.address) {
-                cfg.set_device_config(BudsConfig::new(i.address.clone()))
-                    .await
-                    .unwrap();
+                let mut device_config = BudsConfig::new(i.address.clone());
+                device_config.name = i.name.clone();
+                if let Err(err) = cfg.set_device_config(device_config).await {
+                    eprintln!("Err saving config: {}", err);
+                }
+            } else if i.name.is_some() {
+                // Remember the bluetooth name to allow selecting the device by it
+                let device_config = cfg.get_device_config_mut(&i.address).unwrap();
+                if device_config.name != i.name {
+                    device_config.name = i.name.clone();
+                    if let Err(err) = cfg.save().await {
+                        eprintln!("Err saving config: {}", err);
+                    }
+                }
             }
         }
 
+        notification::notify(NotificationEvent::Connected, &i.address, i.model, &config).await;
+
         // Create a new buds connection task
-        async_std::task::spawn(bean_connection::listener::start_listen(
+        async_std::task::spawn(supervise(
             connection.unwrap(),
             Arc::clone(&config),
             Arc::clone(&arc_ch),
             i.model,
+            recorder.clone(),
+            stop,
+            // Simulated devices can't reconnect
+            i.transport.is_none(),
         ));
     }
 }
 
+// Run the listener of a device. If the RFCOMM link drops, try to reconnect
+// as long as the device is still connected at the bluetooth level
+async fn supervise(
+    connection: BudsConnection,
+    config: Arc<Mutex<Config>>,
+    ch: Arc<Mutex<ConnHandler>>,
+    model: Model,
+    recorder: Option<Arc<Recorder>>,
+    stop: Arc<AtomicBool>,
+    reconnect: bool,
+) {
+    let address = connection.addr.clone();
+    let mut connection = connection;
+
+    loop {
+        let exit = bean_connection::listener::start_listen(
+            connection,
+            Arc::clone(&config),
+            Arc::clone(&ch),
+            model,
+            recorder.clone(),
+        )
+        .await;
+
+        // The device got removed already
+        if exit == ListenerExit::Disconnected || stop.load(Ordering::SeqCst) {
+            return;
+        }
+
+        if !reconnect {
+            break;
+        }
+
+        println!("Lost connection to {}", address);
+        let adapter = config.lock().await.adapter_for(&address);
+        connection = match reconnect_rfcomm(&address, adapter, &ch, &stop).await {
+            Some(connection) => connection,
+            None => break,
+        };
+        println!("Reconnected to {}", address);
+    }
+
+    // Don't remove the device if it got removed and connected again in the meantime
+    if !stop.load(Ordering::SeqCst) {
+        ch.lock().await.remove_device(&address).await;
+    }
+}
+
+// Retry to connect with an exponential backoff. Gives up once
+// the device isn't connected at the bluetooth level anymore
+async fn reconnect_rfcomm(
+    address: &str,
+    adapter: Option<String>,
+    ch: &Arc<Mutex<ConnHandler>>,
+    stop: &AtomicBool,
+) -> Option<BudsConnection> {
+    let mut delay = RECONNECT_DELAY;
+
+    loop {
+        ch.lock()
+            .await
+            .set_link_state(address, LinkState::Lost)
+            .await;
+        task::sleep(delay).await;
+
+        if stop.load(Ordering::SeqCst) {
+            return None;
+        }
+
+        let addr = address.to_owned();
+        let adapter_name = adapter.clone();
+        if !task::spawn_blocking(move || {
+            bt_connection_listener::is_device_connected(&addr, adapter_name.as_deref())
+        })
+        .await
+        {
+            return None;
+        }
+
+        ch.lock()
+            .await
+            .set_link_state(address, LinkState::Connecting)
+            .await;
+
+        let addr = address.to_owned();
+        let adapter_name = adapter.clone();
+        match task::spawn_blocking(move || connect_rfcomm(addr, adapter_name.as_deref())).await {
+            Ok(_) if stop.load(Ordering::SeqCst) => return None,
+            Ok(connection) => return Some(connection),
+            Err(err) => eprintln!("Error reconnecting to {}: {}", address, err),
+        }
+
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
 /// Connect to buds live via rfcomm proto
-pub fn connect_rfcomm<S: AsRef<str>>(addr: S) -> Result<BudsConnection, String> {
+pub fn connect_rfcomm<S: AsRef<str>>(
+    addr: S,
+    adapter: Option<&str>,
+) -> Result<BudsConnection, String> {
     let mut socket = BtSocket::new(BtProtocol::RFCOMM).map_err(|e| e.to_string())?;
+
+    // Connect via the configured adapter instead of the one chosen by the kernel
+    if let Some(adapter) = adapter {
+        let adapter_address =
+            bt_connection_listener::get_adapter_address(adapter).map_err(|e| e.to_string())?;
+        bind_to_adapter(&socket, &adapter_address)?;
+    }
+
     let address = BtAddr::from_str(addr.as_ref()).unwrap();
     socket.connect(address).map_err(|e| e.to_string())?;
     // let fd = socket.get_fd();
 
     Ok(BudsConnection {
         addr: addr.as_ref().to_owned(),
-        socket,
+        socket: Arc::new(socket),
         // fd,
     })
 }
 
+// The RFCOMM socket address (struct sockaddr_rc of BlueZ)
+#[repr(C)]
+struct SockaddrRc {
+    rc_family: libc::sa_family_t,
+    rc_bdaddr: [u8; 6],
+    rc_channel: u8,
+}
+
+// Bind an unconnected RFCOMM socket to the adapter with the given address
+fn bind_to_adapter(socket: &BtSocket, adapter_address: &str) -> Result<(), String> {
+    let addr = SockaddrRc {
+        rc_family: libc::AF_BLUETOOTH as libc::sa_family_t,
+        rc_bdaddr: parse_bdaddr(adapter_address)?,
+        // Any channel
+        rc_channel: 0,
+    };
+
+    let stream = socket.get_stream();
+    let res = unsafe {
+        libc::bind(
+            stream.as_raw_fd(),
+            &addr as *const SockaddrRc as *const libc::sockaddr,
+            std::mem::size_of::<SockaddrRc>() as libc::socklen_t,
+        )
+    };
+
+    if res < 0 {
+        return Err(format!(
+            "Can't use adapter {}: {}",
+            adapter_address,
+            std::io::Error::last_os_error()
+        ));
+    }
+
+    Ok(())
+}
+
+// Convert a bluetooth address into the byte order used by the kernel (least significant first)
+fn parse_bdaddr(address: &str) -> Result<[u8; 6], String> {
+    let err = || format!("Invalid bluetooth address: {}", address);
+
+    let parts: Vec<&str> = address.split(':').collect();
+    if parts.len() != 6 {
+        return Err(err());
+    }
+
+    let mut bdaddr = [0u8; 6];
+    for (byte, part) in bdaddr.iter_mut().zip(parts.iter().rev()) {
+        if part.len() != 2 {
+            return Err(err());
+        }
+        *byte = u8::from_str_radix(part, 16).map_err(|_| err())?;
+    }
+
+    Ok(bdaddr)
+}
+
+/// Connection changes reported to the connection handler
+#[derive(Debug, Clone)]
+pub enum ConnectionEvent {
+    Connected(ConnectionEventData),
+    /// The device got disconnected at the bluetooth level
+    Disconnected {
+        address: String,
+    },
+}
+
 #[derive(Debug, Clone)]
 pub struct ConnectionEventData {
     pub address: String,
+    /// The bluetooth name of the device
+    pub name: Option<String>,
     pub model: Model,
+    /// An already established connection to the device
+    pub transport: Option<Arc<dyn BudsTransport>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use async_std::os::unix::net::UnixStream;
+    use std::time::SystemTime;
+
+    const FIRST: &str = "AA:BB:CC:00:00:01";
+    const SECOND: &str = "AA:BB:CC:00:00:02";
+    const OTHER: &str = "11:22:33:00:00:03";
+
+    // Connection data with devices given by their address, readiness
+    // and the seconds since they got used
+    fn connection_data(devices: &[(&str, bool, u64)]) -> ConnectionData {
+        let mut cd = ConnectionData::new();
+        for (address, ready, used) in devices {
+            let (stream, _) = UnixStream::pair().unwrap();
+            let mut info = BudsInfo::new(stream, address, Model::BudsLive);
+            info.inner.ready = *ready;
+            info.last_used = SystemTime::UNIX_EPOCH + Duration::from_secs(*used);
+            cd.data.insert(address.to_string(), info);
+        }
+        cd
+    }
+
+    #[test]
+    fn select_by_address() {
+        let cd = connection_data(&[(FIRST, true, 1), (SECOND, true, 2), (OTHER, false, 3)]);
+
+        assert_eq!(
+            cd.select_device(FIRST, Some(SECOND)),
+            Some(FIRST.to_owned())
+        );
+
+        // Devices which aren't ready or connected can't be selected
+        assert_eq!(cd.select_device(OTHER, None), None);
+        assert_eq!(cd.select_device("44:55:66:00:00:04", None), None);
+    }
+
+    #[test]
+    fn select_default() {
+        let cd = connection_data(&[(FIRST, true, 1), (SECOND, true, 2)]);
+        assert_eq!(cd.select_device("", Some(FIRST)), Some(FIRST.to_owned()));
+    }
+
+    #[test]
+    fn select_most_recently_used() {
+        let cd = connection_data(&[(FIRST, true, 1), (SECOND, true, 2), (OTHER, false, 3)]);
+
+        // The default device isn't connected
+        assert_eq!(cd.select_device("", Some(OTHER)), Some(SECOND.to_owned()));
+        assert_eq!(cd.select_device("", None), Some(SECOND.to_owned()));
+    }
+
+    #[test]
+    fn select_without_ready_devices() {
+        let cd = connection_data(&[(FIRST, false, 1)]);
+
+        // Falls back to the default device
+        assert_eq!(cd.select_device("", Some(OTHER)), Some(OTHER.to_owned()));
+        assert_eq!(cd.select_device("", None), None);
+    }
+
+    #[test]
+    fn select_resolved_device() {
+        let mut config = Config::default();
+        let mut device = BudsConfig::new(SECOND.to_owned());
+        device.alias = Some("work".to_owned());
+        device.name = Some("Galaxy Buds Live".to_owned());
+        config.buds_settings.push(device);
+
+        let cd = connection_data(&[(FIRST, true, 1), (SECOND, true, 2)]);
+        let connected: Vec<String> = cd.data.keys().cloned().collect();
+
+        // Aliases, names and prefixes get resolved by the config first
+        for query in [
+            "work",
+            "galaxy buds live",
+            "AA:BB:CC:00:00:02",
+            "aabbcc000002",
+        ] {
+            let address = config.resolve_device(query, &connected).unwrap();
+            assert_eq!(cd.select_device(&address, None), Some(SECOND.to_owned()));
+        }
+
+        // Both connected devices share the prefix
+        assert!(config.resolve_device("AA:BB", &connected).is_err());
+    }
+
+    #[test]
+    fn bdaddr() {
+        assert_eq!(
+            parse_bdaddr("00:1A:7d:DA:71:13"),
+            Ok([0x13, 0x71, 0xDA, 0x7D, 0x1A, 0x00])
+        );
+
+        for address in [
+            "",
+            "hci0",
+            "00:1A:7D:DA:71",
+            "00:1A:7D:DA:71:1",
+            "00:1A:7D:DA:71:XX",
+        ] {
+            assert!(parse_bdaddr(address).is_err(), "{}", address);
+        }
+    }
 }
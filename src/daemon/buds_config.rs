@@ -1,33 +1,105 @@
 #![allow(dead_code)]
+use super::{buds_info::BudsInfoInner, buds_profile::BudsProfile, hooks::Hooks, utils};
+
+use galaxy_buds_rs::model::Model;
+
 use serde::{Deserialize, Serialize};
 
 use async_std::fs::{self, File};
 use async_std::io::prelude::*;
-use async_std::path::PathBuf;
+use async_std::path::{Path, PathBuf};
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Config {
+    /// The bluetooth adapter to use (e.g. hci1 or its address)
+    pub adapter: Option<String>,
     pub buds_settings: Vec<BudsConfig>,
+    /// Shell commands to run on buds events
+    pub hooks: Option<Hooks>,
+    /// Modification time of the config file when it was read or saved the last time
+    #[serde(skip)]
+    modified: Option<SystemTime>,
+    /// The file the config is read from and saved to, if not the default one
+    #[serde(skip)]
+    file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationUrgency {
+    Low,
+    Normal,
+    Critical,
+}
+
+impl NotificationUrgency {
+    pub fn parse(s: &str) -> Option<NotificationUrgency> {
+        Some(match s.to_lowercase().as_str() {
+            "low" => NotificationUrgency::Low,
+            "normal" => NotificationUrgency::Normal,
+            "critical" => NotificationUrgency::Critical,
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationUrgency::Low => "low",
+            NotificationUrgency::Normal => "normal",
+            NotificationUrgency::Critical => "critical",
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct BudsConfig {
     pub address: String,
+    /// A name chosen by the user to select the device with
+    pub alias: Option<String>,
+    /// The bluetooth name of the device at the time it connected the last time
+    pub name: Option<String>,
+    /// Overrides the model detected when the device connects
+    pub model: Option<String>,
+    /// The bluetooth adapter to use for the device instead of the global one
+    pub adapter: Option<String>,
     pub default: Option<bool>,
     pub low_battery_notification: Option<bool>,
+    /// Battery levels (in percent) of the buds to notify at
+    pub low_battery_thresholds: Option<Vec<i8>>,
+    /// Battery levels (in percent) of the case to notify at
+    pub low_battery_case_thresholds: Option<Vec<i8>>,
+    pub low_battery_urgency: Option<NotificationUrgency>,
+    /// Time in ms after which the notification disappears
+    pub low_battery_timeout: Option<u32>,
+    pub connected_notification: Option<bool>,
+    pub disconnected_notification: Option<bool>,
+    pub anc_notification: Option<bool>,
+    pub ambient_sound_notification: Option<bool>,
+    pub fully_charged_notification: Option<bool>,
     pub auto_resume_music: Option<bool>,
     pub auto_pause_music: Option<bool>,
     pub smart_sink: Option<bool>,
     pub smart_touchpad: Option<bool>,
     pub hold_to_disconnect: Option<bool>,
+    pub restore_settings: Option<bool>,
+    /// Settings of the device at the time it was used the last time
+    pub last_state: Option<BudsProfile>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, BudsProfile>,
 }
 
 impl Config {
     /// Create a new config object
     pub async fn new() -> Result<Self, String> {
-        let config_file = Self::get_config_file().await?;
+        Self::new_at(Self::get_config_file().await?).await
+    }
 
-        let config;
+    /// Create a new config object using the given file
+    async fn new_at(config_file: PathBuf) -> Result<Self, String> {
+        let mut config: Self;
 
         if !config_file.exists().await
             // Check if file is empty
@@ -37,44 +109,85 @@ impl Config {
                 .unwrap_or(1)
                 == 0
         {
-            config = Self::default();
+            // An empty file may exist already
+            config = Self {
+                modified: get_modified(&config_file).await,
+                file: Some(config_file),
+                ..Self::default()
+            };
             config.save().await?;
         } else {
+            let modified = get_modified(&config_file).await;
             let conf_data = fs::read_to_string(&config_file)
                 .await
                 .map_err(|e| e.to_string())?;
 
             config = toml::from_str(&conf_data).map_err(|e| e.to_string())?;
+            config.modified = modified;
+            config.file = Some(config_file);
         }
 
         Ok(config)
     }
 
     // Save the config
-    pub async fn save(&self) -> Result<(), String> {
-        let config_file = Self::get_config_file().await?;
+    pub async fn save(&mut self) -> Result<(), String> {
+        let config_file = self.config_file().await?;
+
+        // Don't overwrite changes the user made to the file since it got read. Valid
+        // changes get picked up by the config watcher, invalid ones have to be fixed
+        let modified = get_modified(&config_file).await;
+        if modified.is_some() && modified != self.modified {
+            return Err(match Self::read_from(&config_file).await {
+                Ok(_) => "The config file changed and wasn't reloaded yet".to_owned(),
+                Err(err) => format!("Not overwriting the invalid config file: {}", err),
+            });
+        }
 
         let s = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
-        let mut f = File::create(&config_file)
+
+        // Write to a temporary file first, so the config watcher
+        // never sees a partially written config
+        let tmp_file = config_file.with_extension("toml.tmp");
+        let mut f = File::create(&tmp_file)
             .await
             .map_err(|e| e.to_string())?;
         f.write_all(&s.as_bytes())
             .await
             .map_err(|e| e.to_string())?;
+        f.sync_all().await.map_err(|e| e.to_string())?;
 
+        fs::rename(&tmp_file, &config_file)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        self.modified = get_modified(&config_file).await;
         Ok(())
     }
 
-    // load a config
+    // load a config. The current config is kept if the new one is invalid
     pub async fn load(&mut self) -> Result<(), String> {
-        let config_file = Self::get_config_file().await?;
+        *self = Self::read_from(&self.config_file().await?).await?;
+        Ok(())
+    }
+
+    /// Read and validate the config file
+    pub async fn read() -> Result<Self, String> {
+        Self::read_from(&Self::get_config_file().await?).await
+    }
 
-        let conf_data = fs::read_to_string(&config_file)
+    /// Read and validate the given config file
+    async fn read_from(config_file: &Path) -> Result<Self, String> {
+        let modified = get_modified(config_file).await;
+        let conf_data = fs::read_to_string(config_file)
             .await
             .map_err(|e| e.to_string())?;
-        *self = toml::from_str(&conf_data).map_err(|e| e.to_string())?;
+        let mut config: Self = toml::from_str(&conf_data).map_err(|e| e.to_string())?;
 
-        self.check_config()
+        config.check_config()?;
+        config.modified = modified;
+        config.file = Some(config_file.to_owned());
+        Ok(config)
     }
 
     // Check the validity of the config file
@@ -98,11 +211,48 @@ impl Config {
                     device.address
                 ));
             }
+
+            // Check if an alias is used more than once
+            if let Some(ref alias) = device.alias {
+                if self
+                    .buds_settings
+                    .iter()
+                    .filter(|i| i.has_alias(alias))
+                    .count()
+                    > 1
+                {
+                    return Err(format!("Alias '{}' is used more than one time!", alias));
+                }
+            }
+
+            // Check the model override
+            if let Some(ref model) = device.model {
+                utils::parse_model(model)
+                    .map_err(|e| format!("{} configured for device {}", e, device.address))?;
+            }
+
+            // Check the battery thresholds
+            if device
+                .low_battery_thresholds()
+                .iter()
+                .chain(device.low_battery_case_thresholds().iter())
+                .any(|i| !(1..=100).contains(i))
+            {
+                return Err(format!(
+                    "Battery thresholds of device {} have to be between 1 and 100",
+                    device.address
+                ));
+            }
         }
 
         Ok(())
     }
 
+    /// Modification time of the config file when it was read or saved the last time
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
     /// Get configuration for a given device
     pub fn get_device_config(&self, address: &str) -> Option<&BudsConfig> {
         for i in &self.buds_settings {
@@ -138,28 +288,144 @@ impl Config {
         None
     }
 
+    /// Get the address of a device by its address, alias, bluetooth name or a
+    /// unique prefix of its address. 'connected' contains the addresses of all
+    /// connected devices which might not have a config entry yet
+    pub fn resolve_device(&self, query: &str, connected: &[String]) -> Result<String, String> {
+        let mut addresses: Vec<&str> = self
+            .buds_settings
+            .iter()
+            .map(|i| i.address.as_str())
+            .collect();
+        for address in connected {
+            if !addresses.iter().any(|i| i.eq_ignore_ascii_case(address)) {
+                addresses.push(address);
+            }
+        }
+
+        let by_address: Vec<&str> = addresses
+            .iter()
+            .filter(|i| i.eq_ignore_ascii_case(query))
+            .copied()
+            .collect();
+
+        let by_alias: Vec<&str> = self
+            .buds_settings
+            .iter()
+            .filter(|i| i.has_alias(query))
+            .map(|i| i.address.as_str())
+            .collect();
+
+        let by_name: Vec<&str> = self
+            .buds_settings
+            .iter()
+            .filter(|i| {
+                i.name
+                    .as_ref()
+                    .map(|name| name.eq_ignore_ascii_case(query))
+                    .unwrap_or(false)
+            })
+            .map(|i| i.address.as_str())
+            .collect();
+
+        // Allow typing a prefix with or without separators
+        let normalize = |s: &str| s.to_lowercase().replace(&[':', '-'][..], "");
+        let prefix = normalize(query);
+        let by_prefix: Vec<&str> = addresses
+            .iter()
+            .filter(|i| !prefix.is_empty() && normalize(i).starts_with(&prefix))
+            .copied()
+            .collect();
+
+        // Use the most specific way of selecting a device which matches anything
+        let found = [by_address, by_alias, by_name, by_prefix]
+            .iter()
+            .find(|i| !i.is_empty())
+            .cloned()
+            .unwrap_or_default();
+
+        match found.len() {
+            0 => Err(format!("Device '{}' not found", query)),
+            1 => Ok(found[0].to_owned()),
+            _ => Err(format!(
+                "'{}' is ambiguous. It matches the devices {}",
+                query,
+                found.join(", ")
+            )),
+        }
+    }
+
+    /// Get the bluetooth adapter to use for a device
+    pub fn adapter_for(&self, address: &str) -> Option<String> {
+        self.get_device_config(address)
+            .and_then(|i| i.adapter.clone())
+            .or_else(|| self.adapter.clone())
+    }
+
+    /// Get the adapters configured for single devices which differ from the global one
+    pub fn device_adapters(&self) -> Vec<String> {
+        let mut adapters: Vec<String> = vec![];
+        for adapter in self.buds_settings.iter().filter_map(|i| i.adapter.as_ref()) {
+            let mut known = self.adapter.iter().chain(adapters.iter());
+            if !known.any(|i| i.eq_ignore_ascii_case(adapter)) {
+                adapters.push(adapter.clone());
+            }
+        }
+        adapters
+    }
+
     /// Return defaut device if available
     pub fn get_default_device(&self) -> Option<&BudsConfig> {
         self.buds_settings.iter().find(|i| i.is_default())
     }
 
+    /// Remember the current settings of a device to restore them after it
+    /// reconnected. Only done for devices which have restore_settings enabled
+    pub async fn save_last_state(&mut self, info: &BudsInfoInner) -> Result<(), String> {
+        let mut cfg = match self.get_device_config(&info.address) {
+            Some(cfg) if cfg.restore_settings() => cfg.clone(),
+            _ => return Ok(()),
+        };
+
+        let state = BudsProfile::from_info(info);
+        if cfg.last_state.as_ref() == Some(&state) {
+            return Ok(());
+        }
+
+        cfg.last_state = Some(state);
+        self.set_device_config(cfg).await
+    }
+
     /// Set the config of a specific device. If the config
-    /// entry does not exist yet, it will be added
+    /// entry does not exist yet, it will be added. Nothing
+    /// gets changed if the config can't be saved
     pub async fn set_device_config(&mut self, config: BudsConfig) -> Result<(), String> {
-        if self.has_device_config(config.address.clone().as_str()) {
-            let pos = self.get_device_config_pos(config.address.as_str()).unwrap();
-            self.buds_settings[pos] = config;
+        let mut new = self.clone();
+
+        if new.has_device_config(config.address.clone().as_str()) {
+            let pos = new.get_device_config_pos(config.address.as_str()).unwrap();
+            new.buds_settings[pos] = config;
         } else {
             // Set device = default if no other devices found
             let mut config = config;
-            if self.buds_settings.is_empty() {
+            if new.buds_settings.is_empty() {
                 config.default = Some(true);
             }
 
-            self.buds_settings.push(config);
+            new.buds_settings.push(config);
         }
 
-        self.save().await
+        new.save().await?;
+        *self = new;
+        Ok(())
+    }
+
+    // The file the config gets saved to
+    async fn config_file(&self) -> Result<PathBuf, String> {
+        match self.file {
+            Some(ref file) => Ok(file.clone()),
+            None => Self::get_config_file().await,
+        }
     }
 
     // Create missing folders and return the config file
@@ -179,10 +445,19 @@ impl Config {
     }
 }
 
+/// Get the last modification time of a file
+pub async fn get_modified(file: &Path) -> Option<SystemTime> {
+    fs::metadata(file).await.ok()?.modified().ok()
+}
+
 pub fn get_xdg_config() -> Option<PathBuf> {
     try_env_var("XDG_CONFIG_HOME")
 }
 
+pub fn get_xdg_data() -> Option<PathBuf> {
+    try_env_var("XDG_DATA_HOME")
+}
+
 pub fn get_home_dir() -> Option<PathBuf> {
     try_env_var("HOME")
 }
@@ -217,6 +492,39 @@ impl BudsConfig {
         self.low_battery_notification.unwrap_or(false)
     }
 
+    pub fn low_battery_thresholds(&self) -> Vec<i8> {
+        self.low_battery_thresholds.clone().unwrap_or_else(|| vec![20])
+    }
+
+    pub fn low_battery_case_thresholds(&self) -> Vec<i8> {
+        self.low_battery_case_thresholds.clone().unwrap_or_default()
+    }
+
+    pub fn low_battery_urgency(&self) -> NotificationUrgency {
+        self.low_battery_urgency
+            .unwrap_or(NotificationUrgency::Normal)
+    }
+
+    pub fn connected_notification(&self) -> bool {
+        self.connected_notification.unwrap_or(false)
+    }
+
+    pub fn disconnected_notification(&self) -> bool {
+        self.disconnected_notification.unwrap_or(false)
+    }
+
+    pub fn anc_notification(&self) -> bool {
+        self.anc_notification.unwrap_or(false)
+    }
+
+    pub fn ambient_sound_notification(&self) -> bool {
+        self.ambient_sound_notification.unwrap_or(false)
+    }
+
+    pub fn fully_charged_notification(&self) -> bool {
+        self.fully_charged_notification.unwrap_or(false)
+    }
+
     pub fn smart_touchpad(&self) -> bool {
         self.smart_touchpad.unwrap_or(false)
     }
@@ -225,7 +533,176 @@ impl BudsConfig {
         self.smart_sink.unwrap_or(false)
     }
 
+    pub fn hold_to_disconnect(&self) -> bool {
+        self.hold_to_disconnect.unwrap_or(false)
+    }
+
+    pub fn restore_settings(&self) -> bool {
+        self.restore_settings.unwrap_or(false)
+    }
+
+    /// The model configured for the device, if any
+    pub fn model(&self) -> Option<Model> {
+        self.model.as_deref().and_then(|i| utils::parse_model(i).ok())
+    }
+
+    /// Returns true if the device has the given alias (ignoring the case)
+    pub fn has_alias(&self, alias: &str) -> bool {
+        self.alias
+            .as_ref()
+            .map(|i| i.eq_ignore_ascii_case(alias))
+            .unwrap_or(false)
+    }
+
     pub fn is_default(&self) -> bool {
         self.default.unwrap_or(false)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        let device = |address: &str, alias: Option<&str>, name: Option<&str>| BudsConfig {
+            alias: alias.map(|i| i.to_owned()),
+            name: name.map(|i| i.to_owned()),
+            ..BudsConfig::new(address.to_owned())
+        };
+
+        Config {
+            buds_settings: vec![
+                device(
+                    "AA:BB:CC:00:00:01",
+                    Some("work"),
+                    Some("Galaxy Buds Live (0001)"),
+                ),
+                device(
+                    "AA:BB:CC:00:00:02",
+                    Some("home"),
+                    Some("Galaxy Buds Pro (0002)"),
+                ),
+                device("11:22:33:00:00:03", None, Some("home")),
+            ],
+            ..Config::default()
+        }
+    }
+
+    fn resolve(query: &str) -> Result<String, String> {
+        config().resolve_device(query, &["44:55:66:00:00:04".to_owned()])
+    }
+
+    #[test]
+    fn resolve_by_address() {
+        assert_eq!(resolve("AA:BB:CC:00:00:02").unwrap(), "AA:BB:CC:00:00:02");
+        assert_eq!(resolve("aa:bb:cc:00:00:01").unwrap(), "AA:BB:CC:00:00:01");
+
+        // Connected devices without config entry
+        assert_eq!(resolve("44:55:66:00:00:04").unwrap(), "44:55:66:00:00:04");
+    }
+
+    #[test]
+    fn resolve_by_alias() {
+        assert_eq!(resolve("work").unwrap(), "AA:BB:CC:00:00:01");
+        assert_eq!(resolve("WORK").unwrap(), "AA:BB:CC:00:00:01");
+
+        // Aliases win over bluetooth names
+        assert_eq!(resolve("home").unwrap(), "AA:BB:CC:00:00:02");
+    }
+
+    #[test]
+    fn resolve_by_name() {
+        assert_eq!(
+            resolve("galaxy buds pro (0002)").unwrap(),
+            "AA:BB:CC:00:00:02"
+        );
+    }
+
+    #[test]
+    fn resolve_by_prefix() {
+        assert_eq!(resolve("11:22").unwrap(), "11:22:33:00:00:03");
+        assert_eq!(resolve("112233").unwrap(), "11:22:33:00:00:03");
+        assert_eq!(resolve("44-55").unwrap(), "44:55:66:00:00:04");
+    }
+
+    #[test]
+    fn resolve_ambiguous_prefix() {
+        let err = resolve("AA:BB").unwrap_err();
+        assert!(err.contains("ambiguous"), "{}", err);
+        assert!(err.contains("AA:BB:CC:00:00:01") && err.contains("AA:BB:CC:00:00:02"));
+    }
+
+    #[test]
+    fn keep_changed_config_file() {
+        let dir = std::env::temp_dir().join(format!("earbuds-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = PathBuf::from(dir.join("config.toml"));
+
+        // Edit the file like the user would, with a modification time differing from the
+        // one of the last save, no matter the resolution of the file system
+        let edit = |content: &str| {
+            std::fs::write(&file, content).unwrap();
+            let modified = SystemTime::now() + std::time::Duration::from_secs(60);
+            std::fs::File::options()
+                .write(true)
+                .open(&file)
+                .and_then(|f| f.set_modified(modified))
+                .unwrap();
+        };
+
+        async_std::task::block_on(async {
+            // Empty config files get replaced
+            edit("");
+            let mut config = Config::new_at(file.clone()).await.unwrap();
+            config.adapter = Some("hci1".to_owned());
+            config.save().await.unwrap();
+            assert!(fs::read_to_string(&file).await.unwrap().contains("hci1"));
+
+            // Invalid edits of the user block saving until they got fixed
+            edit("adapter = [");
+            let err = config.save().await.unwrap_err();
+            assert!(err.contains("invalid"), "{}", err);
+            assert_eq!(fs::read_to_string(&file).await.unwrap(), "adapter = [");
+
+            // The config stays the same if the changes can't be saved
+            let device = BudsConfig::new("AA:BB:CC:00:00:01".to_owned());
+            assert!(config.set_device_config(device).await.is_err());
+            assert!(config.buds_settings.is_empty());
+
+            // Valid edits block saving until they got reloaded
+            edit("adapter = \"hci2\"\nbuds_settings = []\n");
+            assert!(config.save().await.is_err());
+
+            config.load().await.unwrap();
+            assert_eq!(config.adapter.as_deref(), Some("hci2"));
+            config.save().await.unwrap();
+        });
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn device_adapters() {
+        let mut config = config();
+        assert!(config.device_adapters().is_empty());
+
+        config.adapter = Some("hci0".to_owned());
+        for (device, adapter) in config
+            .buds_settings
+            .iter_mut()
+            .zip(["HCI0", "hci1", "hci1"])
+        {
+            device.adapter = Some(adapter.to_owned());
+        }
+        assert_eq!(config.device_adapters(), vec!["hci1"]);
+
+        config.adapter = None;
+        assert_eq!(config.device_adapters(), vec!["HCI0", "hci1"]);
+    }
+
+    #[test]
+    fn resolve_unknown() {
+        assert!(resolve("office").is_err());
+        assert!(resolve("").is_err());
+    }
+}
@@ -0,0 +1,200 @@
+use super::buds_info::{placement_dser, BudsInfoInner, LinkState, LowBatteryState};
+use super::unix_socket::command::TouchpadSide;
+
+use async_std::channel::{self, Receiver, Sender};
+use galaxy_buds_rs::message::bud_property::Placement;
+use serde::{Deserialize, Serialize};
+
+/// An event which gets pushed to all
+/// subscribed unix socket clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum BudsEvent {
+    Connected {
+        address: String,
+    },
+    Disconnected {
+        address: String,
+    },
+    Battery {
+        address: String,
+        batt_left: i8,
+        batt_right: i8,
+        batt_case: i8,
+    },
+    Placement {
+        address: String,
+        #[serde(with = "placement_dser")]
+        placement_left: Placement,
+        #[serde(with = "placement_dser")]
+        placement_right: Placement,
+    },
+    Anc {
+        address: String,
+        enabled: bool,
+    },
+    AmbientSound {
+        address: String,
+        enabled: bool,
+        volume: u8,
+    },
+    /// The RFCOMM link to the device got lost or reestablished
+    Link {
+        address: String,
+        state: LinkState,
+    },
+    TouchpadAction {
+        address: String,
+        side: TouchpadSide,
+        touch_count: u8,
+    },
+    /// The battery dropped below a configured threshold
+    LowBattery {
+        address: String,
+        threshold: i8,
+        level: i8,
+        /// True if the battery of the case is low
+        case: bool,
+    },
+    /// The config file was changed but is invalid. The previous config is still in use
+    ConfigError {
+        error: String,
+    },
+}
+
+impl BudsEvent {
+    /// Returns the address of the device the event belongs to.
+    /// Empty for events which don't belong to a device
+    pub fn address(&self) -> &str {
+        match self {
+            BudsEvent::Connected { address }
+            | BudsEvent::Disconnected { address }
+            | BudsEvent::Battery { address, .. }
+            | BudsEvent::Placement { address, .. }
+            | BudsEvent::Anc { address, .. }
+            | BudsEvent::AmbientSound { address, .. }
+            | BudsEvent::Link { address, .. }
+            | BudsEvent::TouchpadAction { address, .. }
+            | BudsEvent::LowBattery { address, .. } => address,
+            BudsEvent::ConfigError { .. } => "",
+        }
+    }
+
+    /// Get all events which describe the changes between two states of a device
+    pub fn from_changes(old: &BudsInfoInner, new: &BudsInfoInner) -> Vec<BudsEvent> {
+        let mut events = vec![];
+        let address = new.address.clone();
+
+        if old.batt_left != new.batt_left
+            || old.batt_right != new.batt_right
+            || old.batt_case != new.batt_case
+        {
+            events.push(BudsEvent::Battery {
+                address: address.clone(),
+                batt_left: new.batt_left,
+                batt_right: new.batt_right,
+                batt_case: new.batt_case,
+            });
+        }
+
+        if old.placement_left != new.placement_left || old.placement_right != new.placement_right {
+            events.push(BudsEvent::Placement {
+                address: address.clone(),
+                placement_left: new.placement_left,
+                placement_right: new.placement_right,
+            });
+        }
+
+        if old.noise_reduction != new.noise_reduction {
+            events.push(BudsEvent::Anc {
+                address: address.clone(),
+                enabled: new.noise_reduction,
+            });
+        }
+
+        if old.ambient_sound_enabled != new.ambient_sound_enabled
+            || old.ambient_sound_volume != new.ambient_sound_volume
+        {
+            events.push(BudsEvent::AmbientSound {
+                address: address.clone(),
+                enabled: new.ambient_sound_enabled,
+                volume: new.ambient_sound_volume,
+            });
+        }
+
+        if old.link_state != new.link_state {
+            events.push(BudsEvent::Link {
+                address,
+                state: new.link_state,
+            });
+        }
+
+        events
+    }
+
+    /// Get the events of newly crossed low battery thresholds
+    pub fn from_low_battery(
+        old: &LowBatteryState,
+        new: &LowBatteryState,
+        info: &BudsInfoInner,
+    ) -> Vec<BudsEvent> {
+        let mut events = vec![];
+
+        if let Some(threshold) = new.buds.filter(|_| new.buds != old.buds) {
+            let level = [info.batt_left, info.batt_right]
+                .iter()
+                .filter(|i| **i > 0)
+                .min()
+                .copied()
+                .unwrap_or_default();
+
+            events.push(BudsEvent::LowBattery {
+                address: info.address.clone(),
+                threshold,
+                level,
+                case: false,
+            });
+        }
+
+        if let Some(threshold) = new.case.filter(|_| new.case != old.case) {
+            events.push(BudsEvent::LowBattery {
+                address: info.address.clone(),
+                threshold,
+                level: info.batt_case,
+                case: true,
+            });
+        }
+
+        events
+    }
+}
+
+/// Keeps track of all clients which
+/// are subscribed to buds events
+#[derive(Default)]
+pub struct EventSubscribers {
+    senders: Vec<Sender<BudsEvent>>,
+}
+
+impl EventSubscribers {
+    /// Add a new subscriber
+    pub fn subscribe(&mut self) -> Receiver<BudsEvent> {
+        let (sender, receiver) = channel::unbounded();
+        self.senders.push(sender);
+        receiver
+    }
+
+    /// Send an event to all subscribers. Subscribers
+    /// which went away get removed
+    pub fn publish(&mut self, event: BudsEvent) {
+        self.senders
+            .retain(|sender| sender.try_send(event.clone()).is_ok());
+    }
+
+    /// Publish all changes between two states of a device
+    pub fn publish_changes(&mut self, old: &BudsInfoInner, new: &BudsInfoInner) {
+        for event in BudsEvent::from_changes(old, new) {
+            self.publish(event);
+        }
+    }
+}
@@ -1,3 +1,7 @@
+use super::battery_estimate::{BatteryEstimate, BatteryTracker};
+use super::capture::{Direction, Recorder};
+
+use std::sync::Arc;
 use std::time::SystemTime;
 
 use async_std::io::prelude::*;
@@ -24,6 +28,33 @@ pub struct BudsInfo {
     pub left_tp_hold_count: u8,
     pub right_tp_hold_count: u8,
     pub last_tp_update: SystemTime,
+    pub recorder: Option<Arc<Recorder>>,
+    pub battery_tracker: BatteryTracker,
+    pub low_battery: LowBatteryState,
+    /// The last time the device connected or a command was executed on it
+    pub last_used: SystemTime,
+    /// Set if restoring the last settings failed. The stored
+    /// settings must not be overwritten until they got restored
+    pub restore_failed: bool,
+}
+
+/// State of the RFCOMM link to a device
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkState {
+    #[default]
+    Connected,
+    /// Trying to reconnect after the link got lost
+    Connecting,
+    /// The link got lost while the device is still connected
+    Lost,
+}
+
+/// The lowest battery thresholds a notification was shown for
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LowBatteryState {
+    pub buds: Option<i8>,
+    pub case: Option<i8>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -64,6 +95,13 @@ pub struct BudsInfoInner {
     pub ambient_sound_volume: u8,
     pub extra_high_ambient_volume: bool,
     pub tab_lock_status: ExtTapLockStatus,
+    #[serde(default)]
+    pub battery_estimate: BatteryEstimate,
+    #[serde(default)]
+    pub link_state: LinkState,
+    /// Bluetooth name of the device. Only sent in status responses
+    #[serde(default)]
+    pub name: Option<String>,
 }
 
 impl BudsInfo {
@@ -91,11 +129,19 @@ impl BudsInfo {
                 ambient_sound_volume: 0,
                 extra_high_ambient_volume: false,
                 tab_lock_status: ExtTapLockStatus::default(),
+                battery_estimate: BatteryEstimate::default(),
+                link_state: LinkState::Connected,
+                name: None,
             },
             last_debug: SystemTime::now(),
             left_tp_hold_count: 0,
             right_tp_hold_count: 0,
             last_tp_update: SystemTime::now(),
+            recorder: None,
+            battery_tracker: BatteryTracker::default(),
+            low_battery: LowBatteryState::default(),
+            last_used: SystemTime::now(),
+            restore_failed: false,
         }
     }
 
@@ -145,8 +191,14 @@ impl BudsInfo {
     where
         T: message::Payload,
     {
+        let data = msg.to_byte_array();
+
+        if let Some(ref recorder) = self.recorder {
+            recorder.record(&self.inner.address, self.inner.model, Direction::Out, &data);
+        }
+
         let mut stream = &self.stream;
-        if let Err(err) = stream.write(&msg.to_byte_array()).await {
+        if let Err(err) = stream.write(&data).await {
             return Err(err.to_string());
         }
 
@@ -167,7 +219,7 @@ impl BudsInfoInner {
 }
 
 // Serialize/Deserialize Placement
-mod placement_dser {
+pub mod placement_dser {
     use galaxy_buds_rs::message::bud_property::{BudProperty, Placement};
     use serde::{self, Deserialize, Deserializer, Serializer};
 
@@ -187,7 +239,7 @@ mod placement_dser {
 }
 
 // Serialize/Deserialize EqualizerType
-mod equalizer_dser {
+pub mod equalizer_dser {
     use galaxy_buds_rs::message::bud_property::{BudProperty, EqualizerType};
     use serde::{self, Deserialize, Deserializer, Serializer};
 
@@ -207,7 +259,7 @@ mod equalizer_dser {
 }
 
 // Serialize/Deserialize TouchpadOption
-mod touchpad_option_dser {
+pub mod touchpad_option_dser {
     use galaxy_buds_rs::message::bud_property::{BudProperty, TouchpadOption};
     use serde::{self, Deserialize, Deserializer, Serializer};
 
@@ -228,7 +280,7 @@ mod touchpad_option_dser {
 
 #[derive(Serialize, Deserialize)]
 #[serde(remote = "Model")]
-enum DefModel {
+pub enum DefModel {
     Buds,
     BudsPlus,
     BudsLive,
@@ -0,0 +1,95 @@
+use super::buds_info::BudsInfoInner;
+use super::unix_socket::command::{BudsSetting, TouchpadSide};
+use super::utils;
+
+use galaxy_buds_rs::{
+    message::bud_property::{EqualizerType, TouchpadOption},
+    model::Feature,
+};
+use serde::{Deserialize, Serialize};
+
+/// A set of buds settings which get applied at once.
+/// Unset values are left untouched
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BudsProfile {
+    pub equalizer: Option<String>,
+    pub anc: Option<bool>,
+    pub ambient_sound: Option<u8>,
+    pub touchpad_lock: Option<bool>,
+    pub tap_action_left: Option<String>,
+    pub tap_action_right: Option<String>,
+}
+
+impl BudsProfile {
+    /// Create a profile from the current state of a device
+    pub fn from_info(info: &BudsInfoInner) -> Self {
+        let ambient_sound = if !info.ambient_sound_enabled {
+            0
+        } else if info.extra_high_ambient_volume {
+            4
+        } else {
+            info.ambient_sound_volume
+        };
+
+        Self {
+            equalizer: utils::equalizer_to_str(info.equalizer_type).map(|i| i.to_owned()),
+            anc: Some(info.noise_reduction).filter(|_| info.has_feature(Feature::Anc)),
+            ambient_sound: Some(ambient_sound).filter(|_| info.has_feature(Feature::AmbientSound)),
+            touchpad_lock: Some(info.touchpads_blocked),
+            tap_action_left: utils::tap_action_to_str(info.touchpad_option_left)
+                .map(|i| i.to_owned()),
+            tap_action_right: utils::tap_action_to_str(info.touchpad_option_right)
+                .map(|i| i.to_owned()),
+        }
+    }
+
+    /// Get the settings of the profile in the order they have to be applied
+    pub fn to_settings(&self) -> Result<Vec<BudsSetting>, String> {
+        let mut settings = vec![];
+
+        if let Some(ref equalizer) = self.equalizer {
+            let equalizer_type = utils::str_to_equalizer(equalizer);
+            if equalizer_type == EqualizerType::Undetected {
+                return Err(format!("Invalid equalizer: {}", equalizer));
+            }
+
+            settings.push(BudsSetting::Equalizer {
+                equalizer: equalizer_type,
+            });
+        }
+
+        if let Some(enabled) = self.anc {
+            settings.push(BudsSetting::NoiseReduction { enabled });
+        }
+
+        if let Some(volume) = self.ambient_sound {
+            settings.push(BudsSetting::AmbientVolume { volume });
+        }
+
+        if let Some(locked) = self.touchpad_lock {
+            settings.push(BudsSetting::LockTouchpad {
+                locked,
+                function: None,
+            });
+        }
+
+        for (action, side) in [
+            (&self.tap_action_left, TouchpadSide::Left),
+            (&self.tap_action_right, TouchpadSide::Right),
+        ] {
+            if let Some(action) = action {
+                let option = utils::str_to_tap_action(action);
+                if option == TouchpadOption::Undetected {
+                    return Err(format!("Invalid tap action: {}", action));
+                }
+
+                settings.push(BudsSetting::TouchpadAction {
+                    option,
+                    side: Some(side),
+                });
+            }
+        }
+
+        Ok(settings)
+    }
+}
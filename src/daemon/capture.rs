@@ -0,0 +1,114 @@
+/*
+ * Records the raw traffic between the daemon and the buds, so it
+ * can be attached to bug reports and replayed without hardware
+ */
+
+use super::buds_info::DefModel;
+
+use galaxy_buds_rs::model::Model;
+use serde::{Deserialize, Serialize};
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A single chunk of data read from or written to the buds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureEntry {
+    /// Milliseconds since the unix epoch
+    pub timestamp: u64,
+    pub address: String,
+    #[serde(with = "DefModel")]
+    pub model: Model,
+    pub direction: Direction,
+    /// Hex encoded bytes
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Sent by the buds
+    In,
+    /// Sent to the buds
+    Out,
+}
+
+impl CaptureEntry {
+    /// Get the recorded bytes
+    pub fn bytes(&self) -> Result<Vec<u8>, String> {
+        (0..self.data.len())
+            .step_by(2)
+            .map(|i| {
+                self.data
+                    .get(i..i + 2)
+                    .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                    .ok_or_else(|| format!("Invalid data: {}", self.data))
+            })
+            .collect()
+    }
+}
+
+/// Writes captured traffic as json lines into a file
+#[derive(Debug)]
+pub struct Recorder {
+    file: Mutex<File>,
+}
+
+impl Recorder {
+    /// Create a new recorder appending to the given file
+    pub fn new(path: &str) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Can't open capture file {}: {}", path, e))?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Record a chunk of data
+    pub fn record(&self, address: &str, model: Model, direction: Direction, data: &[u8]) {
+        let entry = CaptureEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|i| i.as_millis() as u64)
+                .unwrap_or_default(),
+            address: address.to_owned(),
+            model,
+            direction,
+            data: data.iter().map(|i| format!("{:02x}", i)).collect(),
+        };
+
+        let mut line = serde_json::to_string(&entry).unwrap();
+        line.push('\n');
+
+        let mut file = self.file.lock().unwrap();
+        if let Err(err) = file.write_all(line.as_bytes()) {
+            eprintln!("Can't write capture: {}", err);
+        }
+    }
+}
+
+/// Read all entries of a capture file
+pub fn read_capture(path: &str) -> Result<Vec<CaptureEntry>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+
+    let mut entries = vec![];
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let entry = serde_json::from_str(&line).map_err(|e| format!("line {}: {}", i + 1, e))?;
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
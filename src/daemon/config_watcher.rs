@@ -0,0 +1,65 @@
+/*
+ * Reloads the config whenever the config file changes
+ */
+
+use super::bluetooth::rfcomm_connector::ConnectionData;
+use super::buds_config::{self, Config};
+use super::buds_event::BudsEvent;
+use super::notification;
+
+use async_std::{
+    sync::{Arc, Mutex},
+    task,
+};
+
+use std::time::Duration;
+
+// Interval in which the config file is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Watch the config file and replace the config of the daemon if it changed.
+/// Invalid configs are reported and the previous config is kept
+pub async fn run(cd: Arc<Mutex<ConnectionData>>, config: Arc<Mutex<Config>>) {
+    let config_file = match Config::get_config_file().await {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("Can't watch config: {}", err);
+            return;
+        }
+    };
+
+    let mut last_modified = buds_config::get_modified(&config_file).await;
+
+    loop {
+        task::sleep(POLL_INTERVAL).await;
+
+        let modified = buds_config::get_modified(&config_file).await;
+        if modified.is_none() || modified == last_modified {
+            continue;
+        }
+        last_modified = modified;
+
+        match Config::read().await {
+            Ok(new_config) => {
+                let mut config = config.lock().await;
+
+                // The file might have been changed while it got read. In that
+                // case the newer version gets read on the next poll
+                if buds_config::get_modified(&config_file).await != new_config.modified() {
+                    continue;
+                }
+
+                *config = new_config;
+                println!("Reloaded config");
+            }
+            Err(err) => {
+                eprintln!("Invalid config, keeping the previous one: {}", err);
+                notification::show_config_error(&err);
+                cd.lock()
+                    .await
+                    .subscribers
+                    .publish(BudsEvent::ConfigError { error: err });
+            }
+        }
+    }
+}
@@ -0,0 +1,396 @@
+/*
+ * Runs user defined shell commands on buds events
+ */
+
+use super::bluetooth::rfcomm_connector::ConnectionData;
+use super::buds_config::Config;
+use super::buds_event::BudsEvent;
+use super::unix_socket::command::TouchpadSide;
+
+use async_std::sync::{Arc, Mutex};
+use galaxy_buds_rs::message::bud_property::Placement;
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::HashMap,
+    process::{Command, Stdio},
+    thread,
+};
+
+/// Shell commands to run when an event occurs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Hooks {
+    pub connected: Option<String>,
+    pub disconnected: Option<String>,
+    /// A bud got put into an ear
+    pub put_in_ear: Option<String>,
+    /// A bud got taken out of an ear
+    pub taken_out: Option<String>,
+    /// A bud got placed into the case
+    pub placed_in_case: Option<String>,
+    /// The battery dropped below one of the low battery thresholds
+    pub battery_low: Option<String>,
+    pub touchpad_action: Option<String>,
+    pub anc_changed: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HookEvent {
+    Connected,
+    Disconnected,
+    PutInEar,
+    TakenOut,
+    PlacedInCase,
+    BatteryLow,
+    TouchpadAction,
+    AncChanged,
+}
+
+impl HookEvent {
+    fn name(self) -> &'static str {
+        match self {
+            HookEvent::Connected => "connected",
+            HookEvent::Disconnected => "disconnected",
+            HookEvent::PutInEar => "put-in-ear",
+            HookEvent::TakenOut => "taken-out",
+            HookEvent::PlacedInCase => "placed-in-case",
+            HookEvent::BatteryLow => "battery-low",
+            HookEvent::TouchpadAction => "touchpad-action",
+            HookEvent::AncChanged => "anc-changed",
+        }
+    }
+
+    fn command(self, hooks: &Hooks) -> Option<&str> {
+        match self {
+            HookEvent::Connected => hooks.connected.as_deref(),
+            HookEvent::Disconnected => hooks.disconnected.as_deref(),
+            HookEvent::PutInEar => hooks.put_in_ear.as_deref(),
+            HookEvent::TakenOut => hooks.taken_out.as_deref(),
+            HookEvent::PlacedInCase => hooks.placed_in_case.as_deref(),
+            HookEvent::BatteryLow => hooks.battery_low.as_deref(),
+            HookEvent::TouchpadAction => hooks.touchpad_action.as_deref(),
+            HookEvent::AncChanged => hooks.anc_changed.as_deref(),
+        }
+    }
+}
+
+type Env = Vec<(&'static str, String)>;
+
+/// Run the configured hooks for all events of all devices
+pub async fn run(cd: Arc<Mutex<ConnectionData>>, config: Arc<Mutex<Config>>) {
+    let receiver = cd.lock().await.subscribers.subscribe();
+
+    // Last known placements of each device
+    let mut placements: HashMap<String, (Placement, Placement)> = HashMap::new();
+
+    while let Ok(event) = receiver.recv().await {
+        let triggered = get_hook_events(&event, &mut placements);
+        if triggered.is_empty() {
+            continue;
+        }
+
+        let hooks = match config.lock().await.hooks.clone() {
+            Some(hooks) => hooks,
+            None => continue,
+        };
+
+        for (hook_event, env) in triggered {
+            if let Some(command) = hook_event.command(&hooks) {
+                execute(command, hook_event, event.address(), env);
+            }
+        }
+    }
+}
+
+// Get the hooks to run for an event together with their event specific environment variables
+fn get_hook_events(
+    event: &BudsEvent,
+    placements: &mut HashMap<String, (Placement, Placement)>,
+) -> Vec<(HookEvent, Env)> {
+    match event {
+        BudsEvent::Connected { .. } => vec![(HookEvent::Connected, vec![])],
+
+        BudsEvent::Disconnected { address } => {
+            placements.remove(address);
+            vec![(HookEvent::Disconnected, vec![])]
+        }
+
+        BudsEvent::Placement {
+            address,
+            placement_left,
+            placement_right,
+        } => {
+            let new = (*placement_left, *placement_right);
+            let old = match placements.insert(address.clone(), new) {
+                Some(old) => old,
+                None => return vec![],
+            };
+
+            let env = vec![
+                ("EARBUDS_PLACEMENT_LEFT", placement_str(new.0).to_owned()),
+                ("EARBUDS_PLACEMENT_RIGHT", placement_str(new.1).to_owned()),
+            ];
+
+            let changes = [(old.0, new.0), (old.1, new.1)];
+            [
+                HookEvent::PutInEar,
+                HookEvent::TakenOut,
+                HookEvent::PlacedInCase,
+            ]
+            .iter()
+            .filter(|hook_event| {
+                changes
+                    .iter()
+                    .any(|(old, new)| is_transition(**hook_event, *old, *new))
+            })
+            .map(|hook_event| (*hook_event, env.clone()))
+            .collect()
+        }
+
+        BudsEvent::LowBattery {
+            threshold,
+            level,
+            case,
+            ..
+        } => vec![(
+            HookEvent::BatteryLow,
+            vec![
+                ("EARBUDS_BATTERY_LEVEL", level.to_string()),
+                ("EARBUDS_BATTERY_THRESHOLD", threshold.to_string()),
+                ("EARBUDS_BATTERY_CASE", bool_str(*case).to_owned()),
+            ],
+        )],
+
+        BudsEvent::TouchpadAction {
+            side, touch_count, ..
+        } => {
+            let side = match side {
+                TouchpadSide::Left => "left",
+                TouchpadSide::Right => "right",
+            };
+
+            vec![(
+                HookEvent::TouchpadAction,
+                vec![
+                    ("EARBUDS_TOUCHPAD_SIDE", side.to_owned()),
+                    ("EARBUDS_TOUCH_COUNT", touch_count.to_string()),
+                ],
+            )]
+        }
+
+        BudsEvent::Anc { enabled, .. } => vec![(
+            HookEvent::AncChanged,
+            vec![("EARBUDS_ANC", bool_str(*enabled).to_owned())],
+        )],
+
+        BudsEvent::Battery { .. }
+        | BudsEvent::AmbientSound { .. }
+        | BudsEvent::Link { .. }
+        | BudsEvent::ConfigError { .. } => vec![],
+    }
+}
+
+// Returns true if a change of the placement of a bud triggers the hook event
+fn is_transition(hook_event: HookEvent, old: Placement, new: Placement) -> bool {
+    // The placement isn't known until the buds sent their first status
+    if old == Placement::Undetected || old == new {
+        return false;
+    }
+
+    let in_case = |p: Placement| p == Placement::InOpenCase || p == Placement::InCloseCase;
+
+    match hook_event {
+        HookEvent::PutInEar => new == Placement::Ear,
+        HookEvent::TakenOut => old == Placement::Ear,
+        HookEvent::PlacedInCase => in_case(new) && !in_case(old),
+        _ => false,
+    }
+}
+
+// Run a hook command in the background
+fn execute(command: &str, hook_event: HookEvent, address: &str, env: Env) {
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("EARBUDS_EVENT", hook_event.name())
+        .env("EARBUDS_ADDRESS", address)
+        .envs(env)
+        .stdin(Stdio::null())
+        .spawn();
+
+    match child {
+        // Wait for the command in a separate thread to not leave zombie processes behind
+        Ok(mut child) => {
+            thread::spawn(move || child.wait());
+        }
+        Err(err) => eprintln!("Can't run {} hook: {}", hook_event.name(), err),
+    }
+}
+
+fn placement_str(placement: Placement) -> &'static str {
+    match placement {
+        Placement::Ear => "ear",
+        Placement::InOpenCase => "open_case",
+        Placement::InCloseCase => "closed_case",
+        Placement::Outside => "outside",
+        _ => "unknown",
+    }
+}
+
+fn bool_str(b: bool) -> &'static str {
+    if b {
+        "1"
+    } else {
+        "0"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use Placement::*;
+
+    const ADDRESS: &str = "00:00:00:00:00:01";
+
+    fn placement(left: Placement, right: Placement) -> BudsEvent {
+        BudsEvent::Placement {
+            address: ADDRESS.to_owned(),
+            placement_left: left,
+            placement_right: right,
+        }
+    }
+
+    fn hook_events(events: &[(HookEvent, Env)]) -> Vec<HookEvent> {
+        events.iter().map(|(hook_event, _)| *hook_event).collect()
+    }
+
+    #[test]
+    fn transitions() {
+        use HookEvent::*;
+
+        // Old and new placement along with the triggered hook events
+        let cases = [
+            (Outside, Ear, vec![PutInEar]),
+            (InOpenCase, Ear, vec![PutInEar]),
+            (Ear, Outside, vec![TakenOut]),
+            (Ear, InOpenCase, vec![TakenOut, PlacedInCase]),
+            (Outside, InOpenCase, vec![PlacedInCase]),
+            (Outside, InCloseCase, vec![PlacedInCase]),
+            // Opening or closing the case doesn't change anything
+            (InOpenCase, InCloseCase, vec![]),
+            (InCloseCase, InOpenCase, vec![]),
+            (InOpenCase, Outside, vec![]),
+            // No change
+            (Ear, Ear, vec![]),
+            (Outside, Outside, vec![]),
+            // The first placement reported by the buds
+            (Undetected, Ear, vec![]),
+            (Undetected, InOpenCase, vec![]),
+        ];
+
+        for (old, new, expected) in cases {
+            let triggered: Vec<HookEvent> = [PutInEar, TakenOut, PlacedInCase]
+                .iter()
+                .copied()
+                .filter(|hook_event| is_transition(*hook_event, old, new))
+                .collect();
+            assert_eq!(triggered, expected, "{:?} -> {:?}", old, new);
+        }
+
+        // Other hook events aren't triggered by placements
+        assert!(!is_transition(Connected, Outside, Ear));
+        assert!(!is_transition(BatteryLow, Ear, InOpenCase));
+    }
+
+    #[test]
+    fn placement_events() {
+        let mut placements = HashMap::new();
+
+        // The previous placement isn't known for the first event
+        assert!(get_hook_events(&placement(Outside, InOpenCase), &mut placements).is_empty());
+
+        let events = get_hook_events(&placement(Ear, InOpenCase), &mut placements);
+        assert_eq!(hook_events(&events), vec![HookEvent::PutInEar]);
+        assert_eq!(
+            events[0].1,
+            vec![
+                ("EARBUDS_PLACEMENT_LEFT", "ear".to_owned()),
+                ("EARBUDS_PLACEMENT_RIGHT", "open_case".to_owned()),
+            ]
+        );
+
+        // No change
+        assert!(get_hook_events(&placement(Ear, InOpenCase), &mut placements).is_empty());
+
+        // Both buds changing trigger each hook event once
+        let events = get_hook_events(&placement(InOpenCase, Ear), &mut placements);
+        assert_eq!(
+            hook_events(&events),
+            vec![
+                HookEvent::PutInEar,
+                HookEvent::TakenOut,
+                HookEvent::PlacedInCase
+            ]
+        );
+
+        // The placement is forgotten once the buds disconnect
+        let disconnected = BudsEvent::Disconnected {
+            address: ADDRESS.to_owned(),
+        };
+        let events = get_hook_events(&disconnected, &mut placements);
+        assert_eq!(hook_events(&events), vec![HookEvent::Disconnected]);
+        assert!(get_hook_events(&placement(Ear, Ear), &mut placements).is_empty());
+    }
+
+    #[test]
+    fn other_events() {
+        let mut placements = HashMap::new();
+
+        let events = get_hook_events(
+            &BudsEvent::TouchpadAction {
+                address: ADDRESS.to_owned(),
+                side: TouchpadSide::Right,
+                touch_count: 2,
+            },
+            &mut placements,
+        );
+        assert_eq!(hook_events(&events), vec![HookEvent::TouchpadAction]);
+        assert_eq!(
+            events[0].1,
+            vec![
+                ("EARBUDS_TOUCHPAD_SIDE", "right".to_owned()),
+                ("EARBUDS_TOUCH_COUNT", "2".to_owned()),
+            ]
+        );
+
+        let events = get_hook_events(
+            &BudsEvent::LowBattery {
+                address: ADDRESS.to_owned(),
+                threshold: 20,
+                level: 18,
+                case: true,
+            },
+            &mut placements,
+        );
+        assert_eq!(hook_events(&events), vec![HookEvent::BatteryLow]);
+        assert_eq!(
+            events[0].1,
+            vec![
+                ("EARBUDS_BATTERY_LEVEL", "18".to_owned()),
+                ("EARBUDS_BATTERY_THRESHOLD", "20".to_owned()),
+                ("EARBUDS_BATTERY_CASE", "1".to_owned()),
+            ]
+        );
+
+        // Events without hooks
+        let battery = BudsEvent::Battery {
+            address: ADDRESS.to_owned(),
+            batt_left: 50,
+            batt_right: 50,
+            batt_case: 50,
+        };
+        assert!(get_hook_events(&battery, &mut placements).is_empty());
+    }
+}
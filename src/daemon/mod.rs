@@ -1,6 +1,14 @@
-mod bluetooth;
-mod buds_config;
+pub mod battery_estimate;
+pub mod battery_history;
+pub mod bluetooth;
+pub mod buds_config;
+pub mod buds_event;
 pub mod buds_info;
+pub mod buds_profile;
+pub mod capture;
+mod config_watcher;
+pub mod hooks;
+pub mod notification;
 pub mod unix_socket;
 pub mod utils;
 
@@ -8,16 +16,29 @@ use async_std::sync::Mutex;
 use bluetooth::rfcomm_connector::ConnectionData;
 
 use std::{
+    process::exit,
     sync::{mpsc, Arc},
     thread,
 };
 
-use self::bluetooth::rfcomm_connector::ConnectionEventData;
+use self::bluetooth::{fake_buds::FakeBudsScript, rfcomm_connector::ConnectionEvent};
+use self::capture::Recorder;
+
+/// Options for running the daemon
+#[derive(Debug, Clone, Default)]
+pub struct DaemonOptions {
+    /// Use the buds simulated by a script instead of bluetooth devices
+    pub fake_buds: Option<String>,
+    /// Replay a capture instead of using bluetooth devices
+    pub replay: Option<String>,
+    /// Record the traffic of all devices into a capture file
+    pub record: Option<String>,
+}
 
 /// Starts the complete daemon
-pub async fn run_daemon(p: String) {
+pub async fn run_daemon(p: String, options: DaemonOptions) {
     // Exchange connection events between bluetooth and connection handler
-    let (conn_tx, conn_rx) = mpsc::channel::<ConnectionEventData>();
+    let (conn_tx, conn_rx) = mpsc::channel::<ConnectionEvent>();
 
     // Exchanging Buds data between unix socket and the buds listener
     let connection_data = Arc::new(Mutex::new(ConnectionData::new()));
@@ -29,6 +50,23 @@ pub async fn run_daemon(p: String) {
             .expect("Couldn't read config"),
     ));
 
+    if let Err(err) = config.lock().await.check_config() {
+        eprintln!("{}", err);
+        exit(1);
+    }
+
+    // Capture setup
+    let recorder = match options.record {
+        Some(ref path) => match Recorder::new(path) {
+            Ok(recorder) => Some(Arc::new(recorder)),
+            Err(err) => {
+                eprintln!("{}", err);
+                exit(1);
+            }
+        },
+        None => None,
+    };
+
     // Run Unix socket listener
     async_std::task::spawn(unix_socket::socket::run(
         p,
@@ -36,18 +74,61 @@ pub async fn run_daemon(p: String) {
         Arc::clone(&config),
     ));
 
+    // Reload the config on changes
+    async_std::task::spawn(config_watcher::run(
+        Arc::clone(&connection_data),
+        Arc::clone(&config),
+    ));
+
+    // Run user hooks
+    async_std::task::spawn(hooks::run(
+        Arc::clone(&connection_data),
+        Arc::clone(&config),
+    ));
+
     // Run connection handler
     async_std::task::spawn(bluetooth::rfcomm_connector::run(
         conn_rx,
         Arc::clone(&connection_data),
         Arc::clone(&config),
+        recorder,
     ));
 
+    // Simulate buds without touching bluetooth
+    let simulated = if let Some(ref script) = options.fake_buds {
+        Some(
+            FakeBudsScript::load(script)
+                .await
+                .and_then(bluetooth::fake_buds::from_script)
+                .map(|i| vec![i]),
+        )
+    } else {
+        options
+            .replay
+            .as_ref()
+            .map(|capture| bluetooth::fake_buds::from_capture(capture))
+    };
+
+    if let Some(events) = simulated {
+        match events {
+            Ok(events) => events
+                .into_iter()
+                .for_each(|i| conn_tx.send(ConnectionEvent::Connected(i)).unwrap()),
+            Err(err) => {
+                eprintln!("Can't simulate buds: {}", err);
+                exit(1);
+            }
+        }
+
+        // Keep running until getting killed
+        return async_std::future::pending().await;
+    }
+
     // Run bluetooth listener
     thread::Builder::new()
         .stack_size(1024 * 1024) // 1MB stack
-        .spawn(|| {
-            bluetooth::bt_connection_listener::run(conn_tx);
+        .spawn(move || {
+            bluetooth::bt_connection_listener::run(conn_tx, config);
         })
         .expect("can't spawn thread")
         .join()
@@ -0,0 +1,123 @@
+/*
+ * Desktop notifications about changes of the buds
+ */
+
+use super::buds_config::{BudsConfig, Config};
+use super::utils::model_name;
+
+use async_std::sync::{Arc, Mutex};
+use galaxy_buds_rs::model::Model;
+use notify_rust::Notification;
+
+/// An event which can be shown as desktop notification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationEvent {
+    Connected,
+    Disconnected,
+    /// ANC got toggled on the buds
+    Anc(bool),
+    /// Ambient sound got toggled on the buds
+    AmbientSound(bool),
+    /// Both buds are fully charged
+    FullyCharged,
+}
+
+impl NotificationEvent {
+    /// Returns true if the notification for the event is enabled in the config
+    pub fn is_enabled(&self, config: &BudsConfig) -> bool {
+        match self {
+            NotificationEvent::Connected => config.connected_notification(),
+            NotificationEvent::Disconnected => config.disconnected_notification(),
+            NotificationEvent::Anc(_) => config.anc_notification(),
+            NotificationEvent::AmbientSound(_) => config.ambient_sound_notification(),
+            NotificationEvent::FullyCharged => config.fully_charged_notification(),
+        }
+    }
+
+    fn to_notification(self, model: Model) -> Notification {
+        let name = model_name(model);
+
+        let (summary, body, icon) = match self {
+            NotificationEvent::Connected => (
+                format!("{} connected", name),
+                format!("Your {} are connected", name),
+                "bluetooth",
+            ),
+            NotificationEvent::Disconnected => (
+                format!("{} disconnected", name),
+                format!("Your {} got disconnected", name),
+                "bluetooth",
+            ),
+            NotificationEvent::Anc(enabled) => (
+                format!("ANC {}", enabled_str(enabled)),
+                format!(
+                    "Noise canceling of your {} is {}",
+                    name,
+                    enabled_str(enabled)
+                ),
+                "audio-headphones",
+            ),
+            NotificationEvent::AmbientSound(enabled) => (
+                format!("Ambient sound {}", enabled_str(enabled)),
+                format!("Ambient sound of your {} is {}", name, enabled_str(enabled)),
+                "audio-headphones",
+            ),
+            NotificationEvent::FullyCharged => (
+                format!("{} fully charged", name),
+                format!("Both of your {} are fully charged", name),
+                "battery-full",
+            ),
+        };
+
+        Notification::new()
+            .summary(&summary)
+            .body(&body)
+            .icon(icon)
+            .to_owned()
+    }
+}
+
+/// Show the notification of an event if it's enabled for the device
+pub fn show(event: NotificationEvent, model: Model, config: &BudsConfig) {
+    if !event.is_enabled(config) {
+        return;
+    }
+
+    if let Err(err) = event.to_notification(model).show() {
+        eprintln!("Can't show notification: {}", err);
+    }
+}
+
+/// Show a notification about an invalid config file
+pub fn show_config_error(err: &str) {
+    let notification = Notification::new()
+        .summary("Invalid earbuds config")
+        .body(&format!("Keeping the previous config: {}", err))
+        .icon("dialog-error")
+        .to_owned();
+
+    if let Err(err) = notification.show() {
+        eprintln!("Can't show notification: {}", err);
+    }
+}
+
+/// Like 'show' but looks up the config of the device first
+pub async fn notify(
+    event: NotificationEvent,
+    address: &str,
+    model: Model,
+    config: &Arc<Mutex<Config>>,
+) {
+    let config = config.lock().await;
+    if let Some(device_config) = config.get_device_config(address) {
+        show(event, model, device_config);
+    }
+}
+
+fn enabled_str(enabled: bool) -> &'static str {
+    if enabled {
+        "enabled"
+    } else {
+        "disabled"
+    }
+}
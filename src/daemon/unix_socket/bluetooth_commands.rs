@@ -1,51 +1,44 @@
-use blurz::{BluetoothAdapter, BluetoothDevice, BluetoothSession};
+use crate::daemon::bluetooth::adapter;
 
-// Connect or disconnect to the buds
-pub async fn change_connection_status<S: AsRef<str>>(device_addr: S, connect: bool) -> String {
+use blurz::{BluetoothDevice, BluetoothSession};
+
+// Connect or disconnect to the buds using the given adapter
+pub async fn change_connection_status<S: AsRef<str>>(
+    device_addr: S,
+    connect: bool,
+    adapter: Option<&str>,
+) -> Result<(), String> {
     // Init bluetooth session and adapter
-    let session = BluetoothSession::create_session(None);
-    if session.is_err() {
-        return format!("Err: {}", session.err().unwrap().to_string());
-    }
-    let session = session.unwrap();
-    let adapter = BluetoothAdapter::init(&session);
-    if adapter.is_err() {
-        return format!("Err: {}", adapter.err().unwrap().to_string());
-    }
-    let adapter = adapter.unwrap();
-    let devices = adapter.get_device_list();
-    if devices.is_err() {
-        return format!("Err: {}", devices.err().unwrap().to_string());
-    }
+    let session = BluetoothSession::create_session(None).map_err(|e| e.to_string())?;
+    let adapter = adapter::get_adapter(&session, adapter).map_err(|e| e.to_string())?;
+    let devices = adapter.get_device_list().map_err(|e| e.to_string())?;
 
     // Find device
     let device = devices
-        .unwrap()
         .iter()
         .map(|i| BluetoothDevice::new(&session, i.clone()))
         .collect::<Vec<BluetoothDevice>>()
         .into_iter()
-        .find(|i| i.get_address().unwrap() == *device_addr.as_ref());
+        .find(|i| {
+            i.get_address()
+                .map(|i| i == *device_addr.as_ref())
+                .unwrap_or(false)
+        });
 
     if device.is_none() {
-        return "Err: device not found!".to_string();
+        return Err("device not found!".to_string());
     }
     let device = device.unwrap();
 
     // Connect or disconnect
-    if let Err(err) = {
-        if connect {
-            if device.is_connected().unwrap_or(false) {
-                return "Device is already connected".to_owned();
-            }
-
-            device.connect(8000)
-        } else {
-            device.disconnect()
+    if connect {
+        if device.is_connected().unwrap_or(false) {
+            return Err("Device is already connected".to_owned());
         }
-    } {
-        return err.to_string();
-    }
 
-    "success".to_string()
+        device.connect(8000)
+    } else {
+        device.disconnect()
+    }
+    .map_err(|e| e.to_string())
 }
@@ -0,0 +1,354 @@
+use super::super::buds_info::{equalizer_dser, touchpad_option_dser};
+use super::super::utils;
+
+use galaxy_buds_rs::message::bud_property::{EqualizerType, Side, TouchpadOption};
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+/// A command which can be executed by the daemon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Command {
+    GetStatus,
+    Subscribe,
+    Connect,
+    Disconnect,
+    SetValue {
+        setting: BudsSetting,
+    },
+    ToggleValue {
+        key: ToggleKey,
+        #[serde(default)]
+        function: Option<TouchpadFunction>,
+    },
+    SetConfig {
+        key: ConfigKey,
+        value: ConfigValue,
+    },
+    GetConfig {
+        key: ConfigKey,
+    },
+    /// Get all config options of the device
+    ListConfig,
+    /// Remove a config option, so its default value gets used
+    UnsetConfig {
+        key: ConfigKey,
+    },
+    /// Apply multiple settings in order. Stops at the first failing setting
+    ApplySettings {
+        settings: Vec<BudsSetting>,
+    },
+    ListProfiles,
+    /// Store the current settings of the device as profile
+    SaveProfile {
+        name: String,
+    },
+    /// Apply the settings of a stored profile
+    LoadProfile {
+        name: String,
+    },
+    DeleteProfile {
+        name: String,
+    },
+    /// List all configured and connected devices
+    ListDevices,
+    /// Make the device the default device
+    SetDefaultDevice,
+}
+
+/// A setting of the buds which can be changed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "key", rename_all = "snake_case")]
+pub enum BudsSetting {
+    NoiseReduction {
+        enabled: bool,
+    },
+    LockTouchpad {
+        locked: bool,
+        /// Lock only a single touchpad function. Only
+        /// supported by models with an extended touchpad lock
+        #[serde(default)]
+        function: Option<TouchpadFunction>,
+    },
+    Equalizer {
+        #[serde(with = "equalizer_dser")]
+        equalizer: EqualizerType,
+    },
+    TouchpadAction {
+        #[serde(with = "touchpad_option_dser")]
+        option: TouchpadOption,
+        /// Set the action for a single side only
+        #[serde(default)]
+        side: Option<TouchpadSide>,
+    },
+    AmbientVolume {
+        volume: u8,
+    },
+}
+
+impl fmt::Display for BudsSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BudsSetting::NoiseReduction { enabled } => write!(f, "ANC: {}", enabled),
+            BudsSetting::LockTouchpad { locked, function } => match function {
+                Some(function) => write!(f, "Touchpad lock ({:?}): {}", function, locked),
+                None => write!(f, "Touchpad lock: {}", locked),
+            },
+            BudsSetting::Equalizer { equalizer } => write!(f, "Equalizer: {:?}", equalizer),
+            BudsSetting::TouchpadAction { option, side } => match side {
+                Some(side) => write!(f, "Tap action ({:?}): {:?}", side, option),
+                None => write!(f, "Tap action: {:?}", option),
+            },
+            BudsSetting::AmbientVolume { volume } => write!(f, "Ambient sound: {}", volume),
+        }
+    }
+}
+
+/// The result of applying a single setting
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingResult {
+    pub setting: BudsSetting,
+    pub status: SettingStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettingStatus {
+    Applied,
+    Failed,
+    /// Not applied since a previous setting failed
+    Skipped,
+}
+
+/// A setting of the buds which can be toggled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToggleKey {
+    NoiseReduction,
+    LockTouchpad,
+}
+
+/// A config option of a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigKey {
+    Alias,
+    Model,
+    Adapter,
+    Default,
+    AutoPause,
+    AutoPlay,
+    SmartSink,
+    SmartTouchpad,
+    HoldToDisconnect,
+    LowBatteryNotification,
+    LowBatteryThresholds,
+    LowBatteryCaseThresholds,
+    LowBatteryUrgency,
+    LowBatteryTimeout,
+    ConnectedNotification,
+    DisconnectedNotification,
+    AncNotification,
+    AmbientSoundNotification,
+    FullyChargedNotification,
+    RestoreSettings,
+}
+
+impl ConfigKey {
+    pub const ALL: [ConfigKey; 20] = [
+        ConfigKey::Alias,
+        ConfigKey::Model,
+        ConfigKey::Adapter,
+        ConfigKey::Default,
+        ConfigKey::AutoPause,
+        ConfigKey::AutoPlay,
+        ConfigKey::SmartSink,
+        ConfigKey::SmartTouchpad,
+        ConfigKey::HoldToDisconnect,
+        ConfigKey::LowBatteryNotification,
+        ConfigKey::LowBatteryThresholds,
+        ConfigKey::LowBatteryCaseThresholds,
+        ConfigKey::LowBatteryUrgency,
+        ConfigKey::LowBatteryTimeout,
+        ConfigKey::ConnectedNotification,
+        ConfigKey::DisconnectedNotification,
+        ConfigKey::AncNotification,
+        ConfigKey::AmbientSoundNotification,
+        ConfigKey::FullyChargedNotification,
+        ConfigKey::RestoreSettings,
+    ];
+
+    /// The name of the key used by the cli
+    pub fn name(self) -> &'static str {
+        match self {
+            ConfigKey::Alias => "alias",
+            ConfigKey::Model => "model",
+            ConfigKey::Adapter => "adapter",
+            ConfigKey::Default => "default",
+            ConfigKey::AutoPause => "auto-pause",
+            ConfigKey::AutoPlay => "auto-play",
+            ConfigKey::SmartSink => "smart-sink",
+            ConfigKey::SmartTouchpad => "smart-touchpad",
+            ConfigKey::HoldToDisconnect => "hold-to-disconnect",
+            ConfigKey::LowBatteryNotification => "low-battery-notification",
+            ConfigKey::LowBatteryThresholds => "low-battery-thresholds",
+            ConfigKey::LowBatteryCaseThresholds => "low-battery-case-thresholds",
+            ConfigKey::LowBatteryUrgency => "low-battery-urgency",
+            ConfigKey::LowBatteryTimeout => "low-battery-timeout",
+            ConfigKey::ConnectedNotification => "connected-notification",
+            ConfigKey::DisconnectedNotification => "disconnected-notification",
+            ConfigKey::AncNotification => "anc-notification",
+            ConfigKey::AmbientSoundNotification => "ambient-sound-notification",
+            ConfigKey::FullyChargedNotification => "fully-charged-notification",
+            ConfigKey::RestoreSettings => "restore-settings",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<ConfigKey> {
+        let s = s.to_lowercase().replace('_', "-");
+        ConfigKey::ALL.iter().copied().find(|i| i.name() == s)
+    }
+
+    /// Parse a value given by the user into the type of the option
+    pub fn parse_value(self, s: &str) -> Option<ConfigValue> {
+        Some(match self {
+            ConfigKey::LowBatteryThresholds | ConfigKey::LowBatteryCaseThresholds => {
+                let thresholds = s
+                    .split(',')
+                    .map(|i| i.trim())
+                    .filter(|i| !i.is_empty())
+                    .map(|i| i.parse::<i8>().ok())
+                    .collect::<Option<Vec<i8>>>()?;
+                ConfigValue::List(thresholds)
+            }
+            ConfigKey::Alias | ConfigKey::Adapter => ConfigValue::Text(s.to_owned()),
+            ConfigKey::LowBatteryUrgency | ConfigKey::Model => ConfigValue::Text(s.to_lowercase()),
+            ConfigKey::LowBatteryTimeout => ConfigValue::Number(s.parse().ok()?),
+            _ => {
+                if !utils::is_str_bool(s) {
+                    return None;
+                }
+                ConfigValue::Bool(utils::str_to_bool(s))
+            }
+        })
+    }
+}
+
+/// The value of a config option
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ConfigValue {
+    Bool(bool),
+    Number(u32),
+    List(Vec<i8>),
+    Text(String),
+}
+
+impl fmt::Display for ConfigValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigValue::Bool(b) => write!(f, "{}", b),
+            ConfigValue::Number(n) => write!(f, "{}", n),
+            ConfigValue::List(list) => {
+                let list: Vec<String> = list.iter().map(|i| i.to_string()).collect();
+                write!(f, "{}", list.join(","))
+            }
+            ConfigValue::Text(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// A config option of a device together with its value
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigEntry {
+    pub key: ConfigKey,
+    /// The value set in the config. None if the default value is used
+    pub value: Option<ConfigValue>,
+    /// The value used if the option isn't set
+    pub default: Option<ConfigValue>,
+}
+
+/// A single function of the touchpads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TouchpadFunction {
+    Tap,
+    DoubleTap,
+    TripleTap,
+    Hold,
+}
+
+impl TouchpadFunction {
+    pub fn parse(s: &str) -> Option<TouchpadFunction> {
+        Some(match s.to_lowercase().as_str() {
+            "tap" => TouchpadFunction::Tap,
+            "doubletap" | "dtap" => TouchpadFunction::DoubleTap,
+            "tripletap" | "ttap" => TouchpadFunction::TripleTap,
+            "hold" => TouchpadFunction::Hold,
+            _ => return None,
+        })
+    }
+}
+
+/// The side of a touchpad
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TouchpadSide {
+    Left,
+    Right,
+}
+
+impl From<Side> for TouchpadSide {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Left => TouchpadSide::Left,
+            Side::Right => TouchpadSide::Right,
+        }
+    }
+}
+
+impl From<TouchpadSide> for Side {
+    fn from(side: TouchpadSide) -> Self {
+        match side {
+            TouchpadSide::Left => Side::Left,
+            TouchpadSide::Right => Side::Right,
+        }
+    }
+}
+
+/// A device known to the daemon, either by its config or by being connected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceEntry {
+    pub address: String,
+    pub alias: Option<String>,
+    /// Bluetooth name of the device
+    pub name: Option<String>,
+    /// Only known if the device is connected
+    pub model: Option<String>,
+    pub connected: bool,
+    pub ready: bool,
+    pub default: bool,
+    pub batt_left: Option<i8>,
+    pub batt_right: Option<i8>,
+    pub batt_case: Option<i8>,
+}
+
+impl DeviceEntry {
+    /// Create an entry of a device which isn't connected
+    pub fn new(address: String) -> Self {
+        Self {
+            address,
+            alias: None,
+            name: None,
+            model: None,
+            connected: false,
+            ready: false,
+            default: false,
+            batt_left: None,
+            batt_right: None,
+            batt_case: None,
+        }
+    }
+}
@@ -1,51 +1,278 @@
-use super::Request;
-use super::{request_handler::get_err, Response};
+use super::command::{ConfigEntry, ConfigKey, ConfigValue};
+use super::{request_handler::get_err, ErrorCode, Response};
 
-use crate::daemon::{buds_config::Config, buds_info::BudsInfoInner, utils};
+use crate::daemon::{
+    buds_config::{BudsConfig, Config, NotificationUrgency},
+    buds_info::BudsInfoInner,
+    utils,
+};
 
 use async_std::sync::{Arc, Mutex};
 
-// Set the value of a config option for a device
-pub async fn set_value(payload: &Request, address: String, config: Arc<Mutex<Config>>) -> String {
-    let mut config = config.lock().await;
+// Set the value of a config option for a device. A value of None
+// removes the option from the config, so its default gets used
+pub async fn set_value(
+    key: ConfigKey,
+    value: Option<ConfigValue>,
+    address: String,
+    config: Arc<Mutex<Config>>,
+) -> String {
+    let mut current = config.lock().await;
+    // Change a copy, so the config stays the same if it can't be saved
+    let mut config = current.clone();
 
-    // Check if device already has a config entry
-    // (should be available but you never know)
-    if !config.has_device_config(&address) {
-        return get_err("Device has no config!");
-    }
-
-    // Check required fields set
-    if payload.opt_param1.is_none() || payload.opt_param2.is_none() {
-        return get_err("Missing parameter");
+    // Aliases have to be unique to select a device by them
+    if let Some(ConfigValue::Text(ref alias)) = value {
+        if key == ConfigKey::Alias
+            && config
+                .buds_settings
+                .iter()
+                .any(|i| i.address != address && i.has_alias(alias))
+        {
+            return get_err(
+                ErrorCode::InvalidValue,
+                &format!("Alias '{}' is already used by another device", alias),
+            );
+        }
     }
 
-    let key = payload.opt_param1.clone().unwrap();
-    let value = utils::str_to_bool(payload.opt_param2.clone().unwrap());
-
     // Get the right config entry mutable
-    let cfg = config.get_device_config_mut(&address);
-    if cfg.is_none() {
-        return get_err("error getting config!");
-    }
-    let cfg = cfg.unwrap();
+    // (should be available but you never know)
+    let cfg = match config.get_device_config_mut(&address) {
+        Some(cfg) => cfg,
+        None => return get_err(ErrorCode::Config, "Device has no config!"),
+    };
 
     // Set the right value of the config
-    match key.as_str() {
-        "auto_pause" => cfg.auto_pause_music = Some(value),
-        "auto_play" => cfg.auto_resume_music = Some(value),
-        "smart_sink" => cfg.smart_sink = Some(value),
-        "low_battery_notification" => cfg.low_battery_notification = Some(value),
-        _ => {
-            return get_err("Invalid key");
-        }
+    if let Err(err) = set_field(cfg, key, value.clone()) {
+        return get_err(ErrorCode::InvalidValue, &err);
+    }
+
+    // Only one device can be the default device
+    if key == ConfigKey::Default && value == Some(ConfigValue::Bool(true)) {
+        config
+            .buds_settings
+            .iter_mut()
+            .filter(|i| i.address != address)
+            .for_each(|i| i.default = None);
     }
 
     // Try to save the config
     if let Err(err) = config.save().await {
-        return get_err(format!("Err saving config: {}", err).as_str());
+        return get_err(
+            ErrorCode::Config,
+            format!("Err saving config: {}", err).as_str(),
+        );
     }
+    *current = config;
 
     let a: Response<BudsInfoInner> = Response::new_success(address.clone(), None);
     serde_json::to_string(&a).unwrap()
 }
+
+// Get a single config option of a device
+pub async fn get_value(key: ConfigKey, address: String, config: Arc<Mutex<Config>>) -> String {
+    let config = config.lock().await;
+
+    let cfg = match config.get_device_config(&address) {
+        Some(cfg) => cfg,
+        None => return get_err(ErrorCode::Config, "Device has no config!"),
+    };
+
+    let response = Response::new_success(address.clone(), Some(get_entry(cfg, key)));
+    serde_json::to_string(&response).unwrap()
+}
+
+// Get all config options of a device
+pub async fn list(address: String, config: Arc<Mutex<Config>>) -> String {
+    let config = config.lock().await;
+
+    let cfg = match config.get_device_config(&address) {
+        Some(cfg) => cfg,
+        None => return get_err(ErrorCode::Config, "Device has no config!"),
+    };
+
+    let entries: Vec<ConfigEntry> = ConfigKey::ALL
+        .iter()
+        .map(|key| get_entry(cfg, *key))
+        .collect();
+
+    let response = Response::new_success(address.clone(), Some(entries));
+    serde_json::to_string(&response).unwrap()
+}
+
+fn get_entry(cfg: &BudsConfig, key: ConfigKey) -> ConfigEntry {
+    ConfigEntry {
+        key,
+        value: get_field(cfg, key),
+        default: get_field(&default_config(), key),
+    }
+}
+
+// A device config with all options set to the values used if they are missing
+fn default_config() -> BudsConfig {
+    let mut cfg = BudsConfig::default();
+    let defaults = BudsConfig::default();
+
+    for key in ConfigKey::ALL.iter() {
+        if let Some(value) = bool_field(&mut cfg, *key) {
+            *value = Some(false);
+        }
+    }
+
+    cfg.low_battery_thresholds = Some(defaults.low_battery_thresholds());
+    cfg.low_battery_case_thresholds = Some(defaults.low_battery_case_thresholds());
+    cfg.low_battery_urgency = Some(defaults.low_battery_urgency());
+    cfg
+}
+
+// Get the value of an option as it is set in the config
+fn get_field(cfg: &BudsConfig, key: ConfigKey) -> Option<ConfigValue> {
+    match key {
+        ConfigKey::LowBatteryThresholds => {
+            cfg.low_battery_thresholds.clone().map(ConfigValue::List)
+        }
+        ConfigKey::LowBatteryCaseThresholds => cfg
+            .low_battery_case_thresholds
+            .clone()
+            .map(ConfigValue::List),
+        ConfigKey::LowBatteryUrgency => cfg
+            .low_battery_urgency
+            .map(|i| ConfigValue::Text(i.as_str().to_owned())),
+        ConfigKey::LowBatteryTimeout => cfg.low_battery_timeout.map(ConfigValue::Number),
+        ConfigKey::Alias => cfg.alias.clone().map(ConfigValue::Text),
+        ConfigKey::Model => cfg.model.clone().map(ConfigValue::Text),
+        ConfigKey::Adapter => cfg.adapter.clone().map(ConfigValue::Text),
+        ConfigKey::Default => cfg.default.map(ConfigValue::Bool),
+        ConfigKey::AutoPause => cfg.auto_pause_music.map(ConfigValue::Bool),
+        ConfigKey::AutoPlay => cfg.auto_resume_music.map(ConfigValue::Bool),
+        ConfigKey::SmartSink => cfg.smart_sink.map(ConfigValue::Bool),
+        ConfigKey::SmartTouchpad => cfg.smart_touchpad.map(ConfigValue::Bool),
+        ConfigKey::HoldToDisconnect => cfg.hold_to_disconnect.map(ConfigValue::Bool),
+        ConfigKey::LowBatteryNotification => cfg.low_battery_notification.map(ConfigValue::Bool),
+        ConfigKey::ConnectedNotification => cfg.connected_notification.map(ConfigValue::Bool),
+        ConfigKey::DisconnectedNotification => cfg.disconnected_notification.map(ConfigValue::Bool),
+        ConfigKey::AncNotification => cfg.anc_notification.map(ConfigValue::Bool),
+        ConfigKey::AmbientSoundNotification => {
+            cfg.ambient_sound_notification.map(ConfigValue::Bool)
+        }
+        ConfigKey::FullyChargedNotification => {
+            cfg.fully_charged_notification.map(ConfigValue::Bool)
+        }
+        ConfigKey::RestoreSettings => cfg.restore_settings.map(ConfigValue::Bool),
+    }
+}
+
+// Set (or unset) the value of an option. Fails if the value has the wrong type
+fn set_field(
+    cfg: &mut BudsConfig,
+    key: ConfigKey,
+    value: Option<ConfigValue>,
+) -> Result<(), String> {
+    let invalid = || format!("Invalid value for {}", key.name());
+
+    match key {
+        ConfigKey::LowBatteryThresholds | ConfigKey::LowBatteryCaseThresholds => {
+            let thresholds = match value {
+                Some(ConfigValue::List(list)) => Some(list),
+                None => None,
+                _ => return Err(invalid()),
+            };
+
+            if let Some(ref thresholds) = thresholds {
+                if thresholds.iter().any(|i| !(1..=100).contains(i)) {
+                    return Err("Battery thresholds have to be between 1 and 100".to_string());
+                }
+            }
+
+            if key == ConfigKey::LowBatteryThresholds {
+                cfg.low_battery_thresholds = thresholds;
+            } else {
+                cfg.low_battery_case_thresholds = thresholds;
+            }
+        }
+
+        ConfigKey::LowBatteryUrgency => {
+            cfg.low_battery_urgency = match value {
+                Some(ConfigValue::Text(s)) => Some(
+                    NotificationUrgency::parse(&s)
+                        .ok_or_else(|| format!("{}. Use low, normal or critical", invalid()))?,
+                ),
+                None => None,
+                _ => return Err(invalid()),
+            };
+        }
+
+        ConfigKey::Alias => {
+            cfg.alias = match value {
+                Some(ConfigValue::Text(alias)) if !alias.trim().is_empty() => Some(alias),
+                None => None,
+                _ => return Err(invalid()),
+            };
+        }
+
+        ConfigKey::Adapter => {
+            cfg.adapter = match value {
+                Some(ConfigValue::Text(adapter)) if !adapter.trim().is_empty() => Some(adapter),
+                None => None,
+                _ => return Err(invalid()),
+            };
+        }
+
+        ConfigKey::Model => {
+            cfg.model = match value {
+                Some(ConfigValue::Text(model)) => {
+                    utils::parse_model(&model)?;
+                    Some(model)
+                }
+                None => None,
+                _ => return Err(invalid()),
+            };
+        }
+
+        ConfigKey::LowBatteryTimeout => {
+            cfg.low_battery_timeout = match value {
+                Some(ConfigValue::Number(timeout)) => Some(timeout),
+                None => None,
+                _ => return Err(invalid()),
+            };
+        }
+
+        _ => {
+            let field = bool_field(cfg, key).ok_or_else(invalid)?;
+            *field = match value {
+                Some(ConfigValue::Bool(b)) => Some(b),
+                None => None,
+                _ => return Err(invalid()),
+            };
+        }
+    }
+
+    Ok(())
+}
+
+// Get the field of a boolean option
+fn bool_field(cfg: &mut BudsConfig, key: ConfigKey) -> Option<&mut Option<bool>> {
+    Some(match key {
+        ConfigKey::Default => &mut cfg.default,
+        ConfigKey::AutoPause => &mut cfg.auto_pause_music,
+        ConfigKey::AutoPlay => &mut cfg.auto_resume_music,
+        ConfigKey::SmartSink => &mut cfg.smart_sink,
+        ConfigKey::SmartTouchpad => &mut cfg.smart_touchpad,
+        ConfigKey::HoldToDisconnect => &mut cfg.hold_to_disconnect,
+        ConfigKey::LowBatteryNotification => &mut cfg.low_battery_notification,
+        ConfigKey::ConnectedNotification => &mut cfg.connected_notification,
+        ConfigKey::DisconnectedNotification => &mut cfg.disconnected_notification,
+        ConfigKey::AncNotification => &mut cfg.anc_notification,
+        ConfigKey::AmbientSoundNotification => &mut cfg.ambient_sound_notification,
+        ConfigKey::FullyChargedNotification => &mut cfg.fully_charged_notification,
+        ConfigKey::RestoreSettings => &mut cfg.restore_settings,
+        ConfigKey::Alias
+        | ConfigKey::Model
+        | ConfigKey::Adapter
+        | ConfigKey::LowBatteryThresholds
+        | ConfigKey::LowBatteryCaseThresholds
+        | ConfigKey::LowBatteryUrgency
+        | ConfigKey::LowBatteryTimeout => return None,
+    })
+}
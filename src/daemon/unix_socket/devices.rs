@@ -0,0 +1,80 @@
+use super::command::DeviceEntry;
+use super::{request_handler::get_err, ErrorCode, Response};
+
+use crate::daemon::{
+    bluetooth::rfcomm_connector::ConnectionData, buds_config::Config, buds_info::BudsInfoInner,
+    utils::model_name,
+};
+
+use async_std::sync::{Arc, Mutex};
+
+// List all devices which have a config entry or are connected
+pub async fn list(connection_data: &ConnectionData, config: &Arc<Mutex<Config>>) -> String {
+    let config = config.lock().await;
+
+    let mut devices: Vec<DeviceEntry> = config
+        .buds_settings
+        .iter()
+        .map(|cfg| DeviceEntry {
+            alias: cfg.alias.clone(),
+            name: cfg.name.clone(),
+            default: cfg.is_default(),
+            ..DeviceEntry::new(cfg.address.clone())
+        })
+        .collect();
+
+    for info in connection_data.data.values() {
+        let pos = match devices.iter().position(|i| i.address == info.inner.address) {
+            Some(pos) => pos,
+            None => {
+                devices.push(DeviceEntry::new(info.inner.address.clone()));
+                devices.len() - 1
+            }
+        };
+
+        let device = &mut devices[pos];
+        device.model = Some(model_name(info.inner.model).to_owned());
+        device.connected = true;
+        device.ready = info.inner.ready;
+
+        // The battery levels are unknown until the device is ready
+        if info.inner.ready {
+            device.batt_left = Some(info.inner.batt_left);
+            device.batt_right = Some(info.inner.batt_right);
+            device.batt_case = Some(info.inner.batt_case);
+        }
+    }
+
+    let response = Response::new_success("", Some(devices));
+    serde_json::to_string(&response).unwrap()
+}
+
+// Make a device the only default device
+pub async fn set_default(address: String, config: &Arc<Mutex<Config>>) -> String {
+    let mut current = config.lock().await;
+    // Change a copy, so the config stays the same if it can't be saved
+    let mut config = current.clone();
+
+    if !config.has_device_config(&address) {
+        return get_err(ErrorCode::Config, "Device has no config!");
+    }
+
+    for cfg in config.buds_settings.iter_mut() {
+        cfg.default = if cfg.address == address {
+            Some(true)
+        } else {
+            None
+        };
+    }
+
+    if let Err(err) = config.save().await {
+        return get_err(
+            ErrorCode::Config,
+            format!("Err saving config: {}", err).as_str(),
+        );
+    }
+    *current = config;
+
+    let response: Response<BudsInfoInner> = Response::new_success(address, None);
+    serde_json::to_string(&response).unwrap()
+}
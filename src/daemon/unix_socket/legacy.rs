@@ -0,0 +1,126 @@
+/*
+ * Compatibility layer for clients which still send the old,
+ * unversioned requests. Will be removed with the next release
+ */
+
+use super::super::utils;
+use super::command::{BudsSetting, Command, ConfigKey, ConfigValue, ToggleKey, TouchpadFunction};
+use super::{CommandError, ErrorCode, Request};
+
+use galaxy_buds_rs::message::bud_property::{BudProperty, EqualizerType, TouchpadOption};
+use serde::Deserialize;
+
+/// Request format used before the protocol got versioned
+#[derive(Debug, Clone, Deserialize)]
+pub struct LegacyRequest {
+    pub cmd: String,
+    pub device: Option<String>,
+    pub opt_param1: Option<String>,
+    pub opt_param2: Option<String>,
+    pub opt_param3: Option<String>,
+}
+
+impl LegacyRequest {
+    /// Convert a legacy request into its typed equivalent
+    pub fn into_request(self) -> Result<Request, CommandError> {
+        let command = match self.cmd.as_str() {
+            "get_status" => Command::GetStatus,
+            "subscribe" => Command::Subscribe,
+            "connect" => Command::Connect,
+            "disconnect" => Command::Disconnect,
+            "set_value" => Command::SetValue {
+                setting: self.parse_setting()?,
+            },
+            "toggle_value" => Command::ToggleValue {
+                key: match self.key()? {
+                    "noise_reduction" => ToggleKey::NoiseReduction,
+                    "lock_touchpad" => ToggleKey::LockTouchpad,
+                    _ => return Err(invalid_value("Invalid key")),
+                },
+                function: self.touchpad_function()?,
+            },
+            "set_config" => Command::SetConfig {
+                key: match self.key()? {
+                    "auto_pause" => ConfigKey::AutoPause,
+                    "auto_play" => ConfigKey::AutoPlay,
+                    "smart_sink" => ConfigKey::SmartSink,
+                    "low_battery_notification" => ConfigKey::LowBatteryNotification,
+                    _ => return Err(invalid_value("Invalid key")),
+                },
+                value: ConfigValue::Bool(utils::str_to_bool(self.value()?)),
+            },
+            _ => {
+                return Err(CommandError::new(
+                    ErrorCode::InvalidRequest,
+                    format!("Unknown command: {}", self.cmd),
+                ))
+            }
+        };
+
+        Ok(Request::new(command, self.device))
+    }
+
+    // Parse the setting of a set_value request
+    fn parse_setting(&self) -> Result<BudsSetting, CommandError> {
+        let value = self.value()?;
+
+        Ok(match self.key()? {
+            "noise_reduction" => BudsSetting::NoiseReduction {
+                enabled: utils::str_to_bool(value),
+            },
+            "lock_touchpad" => BudsSetting::LockTouchpad {
+                locked: utils::str_to_bool(value),
+                function: self.touchpad_function()?,
+            },
+            "equalizer" => BudsSetting::Equalizer {
+                equalizer: EqualizerType::decode(parse_u8(value)?),
+            },
+            "touchpad_action" => BudsSetting::TouchpadAction {
+                option: TouchpadOption::decode(parse_u8(value)?),
+                side: match &self.opt_param3 {
+                    Some(side) => Some(
+                        utils::str_to_side(side)
+                            .ok_or_else(|| invalid_value("Invalid side"))?
+                            .into(),
+                    ),
+                    None => None,
+                },
+            },
+            "ambient_volume" => BudsSetting::AmbientVolume {
+                volume: parse_u8(value)?,
+            },
+            _ => return Err(invalid_value("Invalid key to set to")),
+        })
+    }
+
+    fn key(&self) -> Result<&str, CommandError> {
+        self.opt_param1.as_deref().ok_or_else(missing_parameter)
+    }
+
+    fn value(&self) -> Result<&str, CommandError> {
+        self.opt_param2.as_deref().ok_or_else(missing_parameter)
+    }
+
+    fn touchpad_function(&self) -> Result<Option<TouchpadFunction>, CommandError> {
+        match &self.opt_param3 {
+            Some(function) => TouchpadFunction::parse(function)
+                .map(Some)
+                .ok_or_else(|| invalid_value("Unsupported touchpad function")),
+            None => Ok(None),
+        }
+    }
+}
+
+fn parse_u8(value: &str) -> Result<u8, CommandError> {
+    value
+        .parse::<u8>()
+        .map_err(|_| invalid_value("could not parse value"))
+}
+
+fn missing_parameter() -> CommandError {
+    CommandError::new(ErrorCode::InvalidRequest, "Missing parameter")
+}
+
+fn invalid_value(msg: &str) -> CommandError {
+    CommandError::new(ErrorCode::InvalidValue, msg)
+}
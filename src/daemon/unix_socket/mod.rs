@@ -1,32 +1,74 @@
 pub mod bluetooth_commands;
+pub mod command;
 mod config;
+mod devices;
+mod legacy;
+mod profile;
 pub mod request_handler;
-mod set_value;
+pub mod set_value;
 pub mod socket;
+mod subscribe;
 
+use command::Command;
 use serde::{Deserialize, Serialize};
 
+/// Version of the request/response protocol spoken by the daemon
+pub const PROTOCOL_VERSION: u32 = 1;
+
 /// Unix connection request
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Request {
-    pub cmd: String,
+    pub version: u32,
     pub device: Option<String>,
-    pub opt_param1: Option<String>,
-    pub opt_param2: Option<String>,
-    pub opt_param3: Option<String>,
+    #[serde(flatten)]
+    pub command: Command,
 }
 
 impl Request {
-    pub fn new(cmd: String, device: Option<String>) -> Request {
+    pub fn new(command: Command, device: Option<String>) -> Request {
         Request {
-            cmd,
+            version: PROTOCOL_VERSION,
             device,
-            opt_param1: None,
-            opt_param2: None,
-            opt_param3: None,
+            command,
         }
     }
 
+    /// Parse a request sent by a client. Requests without
+    /// a version get parsed using the legacy format
+    pub fn parse(s: &str) -> Result<Request, CommandError> {
+        let value: serde_json::Value = serde_json::from_str(s)
+            .map_err(|e| CommandError::new(ErrorCode::InvalidRequest, e.to_string()))?;
+
+        let version = match value.get("version") {
+            Some(version) => version.as_u64(),
+            None => {
+                return serde_json::from_value::<legacy::LegacyRequest>(value)
+                    .map_err(|e| CommandError::new(ErrorCode::InvalidRequest, e.to_string()))?
+                    .into_request();
+            }
+        };
+
+        if version != Some(PROTOCOL_VERSION as u64) {
+            return Err(CommandError::new(
+                ErrorCode::UnsupportedVersion,
+                format!(
+                    "Unsupported protocol version. The daemon uses version {}",
+                    PROTOCOL_VERSION
+                ),
+            ));
+        }
+
+        serde_json::from_value(value)
+            .map_err(|e| CommandError::new(ErrorCode::InvalidRequest, e.to_string()))
+    }
+
+    /// Returns true if a request is sent in the legacy format
+    pub fn is_legacy(s: &str) -> bool {
+        serde_json::from_str::<serde_json::Value>(s)
+            .map(|i| i.is_object() && i.get("version").is_none())
+            .unwrap_or(false)
+    }
+
     /// Get bytes to send for a request
     pub fn sendable(&self) -> serde_json::Result<String> {
         let mut s = serde_json::to_string(self)?;
@@ -41,8 +83,12 @@ pub struct Response<T>
 where
     T: serde::ser::Serialize,
 {
+    #[serde(default)]
+    pub version: u32,
     pub status: String,
     pub device: String,
+    #[serde(default)]
+    pub error_code: Option<ErrorCode>,
     pub status_message: Option<String>,
     pub payload: Option<T>,
 }
@@ -54,18 +100,27 @@ where
     /// Create new success response
     fn new_success<S: AsRef<str>>(device_addr: S, payload: Option<T>) -> Self {
         Self {
+            version: PROTOCOL_VERSION,
             status: "success".to_owned(),
             device: device_addr.as_ref().to_owned(),
+            error_code: None,
             payload,
             status_message: None,
         }
     }
 
     /// Create new Error response
-    fn new_error<S: AsRef<str>>(device: String, message: S, payload: Option<T>) -> Self {
+    fn new_error<S: AsRef<str>>(
+        device: String,
+        code: ErrorCode,
+        message: S,
+        payload: Option<T>,
+    ) -> Self {
         Self {
+            version: PROTOCOL_VERSION,
             status: "error".to_owned(),
             device,
+            error_code: Some(code),
             payload,
             status_message: Some(message.as_ref().to_owned()),
         }
@@ -83,3 +138,50 @@ where
         self.status == *"success"
     }
 }
+
+/// Machine readable reason of an error response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The request couldn't be parsed
+    InvalidRequest,
+    /// The request uses a protocol version the daemon doesn't understand
+    UnsupportedVersion,
+    /// No device is connected
+    NoDevice,
+    /// The requested device isn't connected or ready
+    DeviceNotFound,
+    /// The model of the device doesn't support the requested feature
+    UnsupportedFeature,
+    /// A passed value is invalid
+    InvalidValue,
+    /// Sending a command to the buds failed
+    SendFailed,
+    /// Reading or writing the config failed
+    Config,
+    /// A bluetooth operation failed
+    Bluetooth,
+}
+
+/// An error which occurred while running a command
+#[derive(Debug, Clone)]
+pub struct CommandError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl CommandError {
+    pub fn new<S: AsRef<str>>(code: ErrorCode, message: S) -> Self {
+        Self {
+            code,
+            message: message.as_ref().to_owned(),
+        }
+    }
+}
+
+// Errors returned by BudsInfo::send
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self::new(ErrorCode::SendFailed, message)
+    }
+}
@@ -0,0 +1,108 @@
+use super::{request_handler::get_err, set_value, ErrorCode, Response};
+
+use crate::daemon::{
+    buds_config::Config,
+    buds_info::{BudsInfo, BudsInfoInner},
+    buds_profile::BudsProfile,
+};
+
+use async_std::sync::{Arc, Mutex};
+
+use std::collections::BTreeMap;
+
+// List all profiles of a device
+pub async fn list(address: String, config: Arc<Mutex<Config>>) -> String {
+    let config = config.lock().await;
+
+    let profiles = match config.get_device_config(&address) {
+        Some(cfg) => cfg.profiles.clone(),
+        None => BTreeMap::new(),
+    };
+
+    let response: Response<BTreeMap<String, BudsProfile>> =
+        Response::new_success(address, Some(profiles));
+    serde_json::to_string(&response).unwrap()
+}
+
+// Store the current state of a device as profile
+pub async fn save(name: &str, device: &BudsInfo, config: Arc<Mutex<Config>>) -> String {
+    if name.trim().is_empty() {
+        return get_err(ErrorCode::InvalidValue, "Profile name can't be empty");
+    }
+
+    let address = device.inner.address.clone();
+    let profile = BudsProfile::from_info(&device.inner);
+
+    let mut current = config.lock().await;
+    // Change a copy, so the config stays the same if it can't be saved
+    let mut config = current.clone();
+    let cfg = match config.get_device_config_mut(&address) {
+        Some(cfg) => cfg,
+        None => return get_err(ErrorCode::Config, "Device has no config!"),
+    };
+    cfg.profiles.insert(name.to_owned(), profile.clone());
+
+    if let Err(err) = config.save().await {
+        return get_err(
+            ErrorCode::Config,
+            format!("Err saving config: {}", err).as_str(),
+        );
+    }
+    *current = config;
+
+    let response = Response::new_success(address, Some(profile));
+    serde_json::to_string(&response).unwrap()
+}
+
+// Apply a stored profile to a device
+pub async fn load(name: &str, device: &mut BudsInfo, config: Arc<Mutex<Config>>) -> String {
+    let profile = {
+        let config = config.lock().await;
+        config
+            .get_device_config(&device.inner.address)
+            .and_then(|cfg| cfg.profiles.get(name).cloned())
+    };
+
+    let profile = match profile {
+        Some(profile) => profile,
+        None => {
+            return get_err(
+                ErrorCode::InvalidValue,
+                format!("Profile '{}' not found", name).as_str(),
+            )
+        }
+    };
+
+    match profile.to_settings() {
+        Ok(settings) => set_value::apply(&settings, device).await,
+        Err(err) => get_err(ErrorCode::InvalidValue, &err),
+    }
+}
+
+// Delete a profile of a device
+pub async fn delete(name: &str, address: String, config: Arc<Mutex<Config>>) -> String {
+    let mut current = config.lock().await;
+    // Change a copy, so the config stays the same if it can't be saved
+    let mut config = current.clone();
+
+    let removed = config
+        .get_device_config_mut(&address)
+        .and_then(|cfg| cfg.profiles.remove(name));
+    if removed.is_none() {
+        return get_err(
+            ErrorCode::InvalidValue,
+            format!("Profile '{}' not found", name).as_str(),
+        );
+    }
+
+    if let Err(err) = config.save().await {
+        return get_err(
+            ErrorCode::Config,
+            format!("Err saving config: {}", err).as_str(),
+        );
+    }
+    *current = config;
+
+    let response: Response<BudsInfoInner> = Response::new_success(address, None);
+    serde_json::to_string(&response).unwrap()
+}
@@ -1,8 +1,8 @@
 use super::super::buds_info::BudsInfoInner;
-use super::set_value;
 use super::{super::bluetooth::rfcomm_connector::ConnectionData, config};
 use super::{super::buds_config::Config, bluetooth_commands};
-use super::{Request, Response};
+use super::{command::Command, devices, profile, set_value, subscribe};
+use super::{ErrorCode, Request, Response};
 
 use async_std::{
     io::{prelude::*, BufReader, BufWriter},
@@ -10,7 +10,11 @@ use async_std::{
     sync::{Arc, Mutex},
 };
 
-/// Handle a unix socket connection
+use std::time::SystemTime;
+
+/// Handle a unix socket connection. Clients can send multiple newline separated
+/// requests over a single connection. Legacy clients read until the connection
+/// gets closed, so it's closed after answering a legacy request
 pub async fn handle_client(
     stream: UnixStream,
     cd: Arc<Mutex<ConnectionData>>,
@@ -19,45 +23,157 @@ pub async fn handle_client(
     let mut read_stream = BufReader::new(&stream);
     let mut write_stream = BufWriter::new(&stream);
 
-    // Read the request
-    let mut buff = String::new();
-    if read_stream.read_line(&mut buff).await.is_err() {
-        return;
+    loop {
+        // Read the next request. Stop once the client closed the connection
+        let mut buff = String::new();
+        match read_stream.read_line(&mut buff).await {
+            Ok(0) | Err(_) => return,
+            Ok(_) => (),
+        }
+
+        // Skip empty lines
+        if buff.trim().is_empty() {
+            continue;
+        }
+
+        if !handle_request(&buff, &mut write_stream, &cd, &config).await
+            || Request::is_legacy(&buff)
+        {
+            return;
+        }
     }
+}
 
+// Handle a single request. Returns false if the connection should be closed
+async fn handle_request(
+    buff: &str,
+    write_stream: &mut BufWriter<&UnixStream>,
+    cd: &Arc<Mutex<ConnectionData>>,
+    config: &Arc<Mutex<Config>>,
+) -> bool {
     // Parse the request
-    let payload = match serde_json::from_str::<Request>(buff.as_str()) {
+    let payload = match Request::parse(buff) {
         Ok(p) => p,
-        Err(_) => return,
+        Err(err) => return respond(get_err(err.code, &err.message), write_stream).await,
     };
 
+    // Subscriptions keep the connection open and don't require a connected device
+    if let Command::Subscribe = payload.command {
+        subscribe::handle(&payload, write_stream, Arc::clone(cd), config).await;
+        return false;
+    }
+
     let mut connection_data = cd.lock().await;
 
+    // Listing and selecting devices works without connected devices
+    match payload.command {
+        Command::ListDevices => {
+            return respond(devices::list(&connection_data, config).await, write_stream).await;
+        }
+        Command::SetDefaultDevice => {
+            let response = match resolve_device(&payload, &connection_data, config).await {
+                Ok(addr) if !addr.is_empty() => devices::set_default(addr, config).await,
+                Ok(_) => get_err(ErrorCode::InvalidRequest, "No device given"),
+                Err(err) => get_err(ErrorCode::DeviceNotFound, &err),
+            };
+            return respond(response, write_stream).await;
+        }
+        _ => (),
+    }
+
     // Respond with error if no device is connected and no connect request was made
-    if connection_data.get_device_count() == 0 && payload.cmd != "connect" {
-        respond(get_err("No connected device found"), &mut write_stream).await;
-        return;
+    if connection_data.get_device_count() == 0 && !matches!(payload.command, Command::Connect) {
+        return respond(
+            get_err(ErrorCode::NoDevice, "No connected device found"),
+            write_stream,
+        )
+        .await;
     }
 
-    let req_dev_addr = payload.device.clone().unwrap_or_default();
+    let req_dev_addr = match resolve_device(&payload, &connection_data, config).await {
+        Ok(addr) => addr,
+        Err(err) => {
+            return respond(get_err(ErrorCode::DeviceNotFound, &err), write_stream).await;
+        }
+    };
+
     let device_addr = match connection_data
-        .get_device_address(&req_dev_addr, &config)
+        .get_device_address(&req_dev_addr, config)
         .await
     {
         Some(addr) => addr,
+        // Devices which aren't connected can still be connected to
+        None if matches!(payload.command, Command::Connect) && !req_dev_addr.is_empty() => {
+            req_dev_addr
+        }
+        None if !req_dev_addr.is_empty() => {
+            let err = format!("Device {} is not connected", req_dev_addr);
+            return respond(
+                with_device(get_err(ErrorCode::DeviceNotFound, &err), &req_dev_addr),
+                write_stream,
+            )
+            .await;
+        }
         None => {
-            respond(get_err("Device not found"), &mut write_stream).await;
-            return;
+            return respond(
+                get_err(ErrorCode::DeviceNotFound, "Device not found"),
+                write_stream,
+            )
+            .await;
         }
     };
 
+    // Remember the device for selecting the most recently used one
+    if let Some(device) = connection_data.get_device_mut(&device_addr) {
+        device.last_used = SystemTime::now();
+    }
+
     // Execute the command
-    let new_payload = run_payload_cmd(&payload, device_addr, &mut connection_data, config).await;
-    if new_payload.is_none() {
-        return;
+    let new_payload = run_payload_cmd(
+        &payload,
+        device_addr.clone(),
+        &mut connection_data,
+        Arc::clone(config),
+    )
+    .await;
+
+    match new_payload {
+        Some(response) => respond(with_device(response, &device_addr), write_stream).await,
+        None => true,
+    }
+}
+
+// Let the client know which device a command was executed on,
+// even if the response is an error
+fn with_device(response: String, device: &str) -> String {
+    let mut value: serde_json::Value = match serde_json::from_str(&response) {
+        Ok(value) => value,
+        Err(_) => return response,
+    };
+
+    if value.get("device").and_then(|i| i.as_str()) != Some("") {
+        return response;
     }
 
-    respond(new_payload.unwrap(), &mut write_stream).await;
+    value["device"] = device.into();
+    value.to_string()
+}
+
+/// Get the address of the device requested by the client. The device can be given as
+/// address, alias, bluetooth name or address prefix. Returns an empty string if the
+/// client didn't request a specific device
+pub async fn resolve_device(
+    payload: &Request,
+    connection_data: &ConnectionData,
+    config: &Arc<Mutex<Config>>,
+) -> Result<String, String> {
+    let query = match payload.device.as_deref() {
+        Some(query) if !query.is_empty() => query,
+        _ => return Ok(String::new()),
+    };
+
+    let connected: Vec<String> = connection_data.data.keys().cloned().collect();
+    config.lock().await.resolve_device(query, &connected)
 }
 
 // Run the requested command
@@ -67,45 +183,102 @@ async fn run_payload_cmd(
     connection_data: &mut ConnectionData,
     config: Arc<Mutex<Config>>,
 ) -> Option<String> {
-    Some(match payload.cmd.as_str() {
-        "get_status" => {
-            let response = Response::new_success(
-                &device_addr,
-                Some(
-                    connection_data
-                        .get_device(&device_addr)
-                        .unwrap()
-                        .inner
-                        .clone(),
-                ),
-            );
+    Some(match &payload.command {
+        Command::GetStatus => {
+            let mut status = connection_data
+                .get_device(&device_addr)
+                .unwrap()
+                .inner
+                .clone();
+
+            // The bluetooth name gets stored in the config when the device connects
+            status.name = config
+                .lock()
+                .await
+                .get_device_config(&device_addr)
+                .and_then(|i| i.name.clone());
+
+            let response = Response::new_success(&device_addr, Some(status));
             serde_json::to_string(&response).unwrap()
         }
-        "set_value" => {
-            let mut device = connection_data.get_device_mut(&device_addr).unwrap();
-            set_value::set(&payload, &mut device).await
+        Command::SetValue { .. }
+        | Command::ToggleValue { .. }
+        | Command::ApplySettings { .. }
+        | Command::LoadProfile { .. } => {
+            let device = connection_data.get_device_mut(&device_addr).unwrap();
+            let old_state = device.inner.clone();
+
+            let response = match payload.command {
+                Command::ToggleValue { key, function } => {
+                    set_value::toggle(key, function, device).await
+                }
+                Command::SetValue { ref setting } => set_value::set(setting, device).await,
+                Command::ApplySettings { ref settings } => set_value::apply(settings, device).await,
+                Command::LoadProfile { ref name } => {
+                    profile::load(name, device, Arc::clone(&config)).await
+                }
+                _ => unreachable!(),
+            };
+
+            // Let subscribers know about the changed values
+            let new_state = device.inner.clone();
+            let restore_failed = device.restore_failed;
+            connection_data
+                .subscribers
+                .publish_changes(&old_state, &new_state);
+
+            // Remember the new settings to restore them on the next connection,
+            // unless the previous ones still have to be restored
+            if !restore_failed {
+                if let Err(err) = config.lock().await.save_last_state(&new_state).await {
+                    eprintln!("Err saving config: {}", err);
+                }
+            }
+
+            response
+        }
+        Command::ListProfiles => profile::list(device_addr, config).await,
+        Command::SaveProfile { name } => {
+            let device = connection_data.get_device(&device_addr).unwrap();
+            profile::save(name, device, config).await
         }
-        "toggle_value" => {
-            let mut device = connection_data.get_device_mut(&device_addr).unwrap();
-            set_value::toggle(&payload, &mut device).await
+        Command::DeleteProfile { name } => profile::delete(name, device_addr, config).await,
+        Command::SetConfig { key, value } => {
+            config::set_value(*key, Some(value.clone()), device_addr, config).await
         }
-        "set_config" => config::set_value(&payload, device_addr.clone(), config).await,
-        "disconnect" | "connect" => {
-            bluetooth_commands::change_connection_status(
-                device_addr.clone(),
-                payload.cmd == "connect",
+        Command::UnsetConfig { key } => config::set_value(*key, None, device_addr, config).await,
+        Command::GetConfig { key } => config::get_value(*key, device_addr, config).await,
+        Command::ListConfig => config::list(device_addr, config).await,
+        Command::Disconnect | Command::Connect => {
+            let connect = matches!(payload.command, Command::Connect);
+            let adapter = config.lock().await.adapter_for(&device_addr);
+            match bluetooth_commands::change_connection_status(
+                &device_addr,
+                connect,
+                adapter.as_deref(),
             )
             .await
+            {
+                Ok(()) => {
+                    let response: Response<BudsInfoInner> =
+                        Response::new_success(&device_addr, None);
+                    serde_json::to_string(&response).unwrap()
+                }
+                Err(err) => get_err(ErrorCode::Bluetooth, &err),
+            }
         }
 
-        _ => return None,
+        // Handled before a device gets selected
+        Command::Subscribe | Command::ListDevices | Command::SetDefaultDevice => return None,
     })
 }
 
-// Respond to client. Return true on success
-async fn respond(response: String, write_stream: &mut BufWriter<&UnixStream>) -> bool {
+// Respond to client. Responses are terminated by a newline. Return true on success
+async fn respond(mut response: String, write_stream: &mut BufWriter<&UnixStream>) -> bool {
+    response.push('\n');
+
     // Write response
-    if let Err(err) = write_stream.write(response.as_bytes()).await {
+    if let Err(err) = write_stream.write_all(response.as_bytes()).await {
         eprintln!("Err: {:?}", err);
         return false;
     }
@@ -119,7 +292,53 @@ async fn respond(response: String, write_stream: &mut BufWriter<&UnixStream>) ->
 }
 
 // Return an serializeable error
-pub fn get_err(msg: &str) -> String {
-    let err: Response<BudsInfoInner> = Response::new_error("".to_owned(), msg.to_owned(), None);
+pub fn get_err(code: ErrorCode, msg: &str) -> String {
+    let err: Response<BudsInfoInner> =
+        Response::new_error("".to_owned(), code, msg.to_owned(), None);
     serde_json::to_string(&err).unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use async_std::{future, task};
+    use std::time::Duration;
+
+    // Send a request and read the responses until the daemon closes
+    // the connection. Returns None if the connection stays open
+    fn send(request: &str) -> Option<String> {
+        task::block_on(async {
+            let (client, daemon) = UnixStream::pair().unwrap();
+            let cd = Arc::new(Mutex::new(ConnectionData::new()));
+            let config = Arc::new(Mutex::new(Config::default()));
+            task::spawn(handle_client(daemon, cd, config));
+
+            let mut client_ref = &client;
+            client_ref.write_all(request.as_bytes()).await.unwrap();
+
+            let mut response = String::new();
+            let mut reader = BufReader::new(&client);
+            let read = reader.read_to_string(&mut response);
+            future::timeout(Duration::from_secs(1), read)
+                .await
+                .ok()?
+                .unwrap();
+            Some(response)
+        })
+    }
+
+    #[test]
+    fn legacy_request_closes_connection() {
+        let response = send("{\"cmd\":\"get_status\"}\n").expect("connection wasn't closed");
+        assert_eq!(response.lines().count(), 1);
+
+        let response = Response::<BudsInfoInner>::from_string(response.trim()).unwrap();
+        assert_eq!(response.error_code, Some(ErrorCode::NoDevice));
+    }
+
+    #[test]
+    fn versioned_request_keeps_connection_open() {
+        assert!(send("{\"version\":1,\"cmd\":\"get_status\"}\n").is_none());
+    }
+}
@@ -1,16 +1,16 @@
 use super::{
-    super::{
-        buds_info::{BudsInfo, BudsInfoInner},
-        utils,
+    super::buds_info::{BudsInfo, BudsInfoInner},
+    command::{
+        BudsSetting, SettingResult, SettingStatus, ToggleKey, TouchpadFunction, TouchpadSide,
     },
     request_handler::get_err,
-    Request, Response,
+    CommandError, ErrorCode, Response,
 };
 
 use galaxy_buds_rs::{
     message::{
         ambient_mode,
-        bud_property::{BudProperty, EqualizerType, Side, TouchpadOption},
+        bud_property::{EqualizerType, Side, TouchpadOption},
         lock_touchpad::{self, ExtLockTouchpad},
         set_noise_reduction, set_touchpad_option,
         simple::new_equalizer,
@@ -18,112 +18,206 @@ use galaxy_buds_rs::{
     model::Feature,
 };
 
-// Parses the payload and runs the actual set-option request
-pub async fn set(payload: &Request, device_data: &mut BudsInfo) -> String {
-    // Check required fields set
-    if payload.opt_param1.is_none() || payload.opt_param2.is_none() {
-        return get_err("Missing parameter");
+// Runs the actual set-option request
+pub async fn set(setting: &BudsSetting, device_data: &mut BudsInfo) -> String {
+    // Run desired command
+    let res = set_buds_option(setting, device_data).await;
+
+    // Return success or error based on the success of the set command
+    match res {
+        Ok(()) => {
+            let a: Response<BudsInfoInner> =
+                Response::new_success(device_data.inner.address.clone(), None);
+            serde_json::to_string(&a).unwrap()
+        }
+        Err(err) => get_err(err.code, &err.message),
     }
+}
 
-    let key = payload.opt_param1.clone().unwrap();
-    let value = payload.opt_param2.clone().unwrap();
+// Apply multiple settings in order. If one of them fails, the remaining ones get skipped
+// and the applied ones get rolled back. Responds with the result of every single setting
+pub async fn apply(settings: &[BudsSetting], device_data: &mut BudsInfo) -> String {
+    let (results, error) = apply_settings(settings, device_data).await;
+    let address = device_data.inner.address.clone();
 
-    // Run desired command
-    let res = set_buds_option(
-        key.as_str(),
-        value.as_str(),
-        device_data,
-        &payload.opt_param3,
-    )
-    .await;
+    let response = match error {
+        None => Response::new_success(address, Some(results)),
+        Some(err) => Response::new_error(address, err.code, err.message, Some(results)),
+    };
+    serde_json::to_string(&response).unwrap()
+}
 
-    // Return success or error based on the success of the set command
-    if res.is_ok() {
-        let a: Response<BudsInfoInner> =
-            Response::new_success(device_data.inner.address.clone(), None);
-        serde_json::to_string(&a).unwrap()
-    } else {
-        get_err(res.err().unwrap().as_str())
+/// Apply multiple settings in order and return the result for each of them along with the
+/// error which made applying them fail. On failure the previous values of the already applied
+/// settings get sent to the buds again and the cached state gets rolled back
+pub async fn apply_settings(
+    settings: &[BudsSetting],
+    device_data: &mut BudsInfo,
+) -> (Vec<SettingResult>, Option<CommandError>) {
+    let old_state = device_data.inner.clone();
+    let mut results = Vec::with_capacity(settings.len());
+    let mut error: Option<CommandError> = None;
+
+    for (i, setting) in settings.iter().enumerate() {
+        let (status, msg) = if error.is_some() {
+            (SettingStatus::Skipped, None)
+        } else {
+            match set_buds_option(setting, device_data).await {
+                Ok(()) => (SettingStatus::Applied, None),
+                Err(err) => {
+                    let msg = err.message.clone();
+                    error = Some(CommandError::new(
+                        err.code,
+                        format!("Failed to apply setting {} ({}): {}", i + 1, setting, msg),
+                    ));
+                    (SettingStatus::Failed, Some(msg))
+                }
+            }
+        };
+
+        results.push(SettingResult {
+            setting: setting.clone(),
+            status,
+            error: msg,
+        });
+    }
+
+    if error.is_some() {
+        let applied = results
+            .iter()
+            .filter(|i| i.status == SettingStatus::Applied)
+            .rev();
+        for result in applied {
+            if let Err(err) = revert_setting(&result.setting, &old_state, device_data).await {
+                eprintln!("Can't roll back {}: {}", result.setting, err);
+            }
+        }
+
+        device_data.inner = old_state;
+    }
+
+    (results, error)
+}
+
+// Send the values a setting had in 'old' to the buds again
+async fn revert_setting(
+    setting: &BudsSetting,
+    old: &BudsInfoInner,
+    buds_info: &mut BudsInfo,
+) -> Result<(), String> {
+    match setting {
+        BudsSetting::NoiseReduction { .. } => {
+            buds_info
+                .send(set_noise_reduction::new(old.noise_reduction))
+                .await
+        }
+
+        BudsSetting::LockTouchpad { .. } if buds_info.has_feature(Feature::ExtTouchpadLock) => {
+            buds_info
+                .send(ExtLockTouchpad::from_ext_tap_lock_status(
+                    old.tab_lock_status,
+                ))
+                .await
+        }
+        BudsSetting::LockTouchpad { .. } => {
+            buds_info
+                .send(lock_touchpad::new(old.touchpads_blocked))
+                .await
+        }
+
+        // Unknown values can't be sent
+        BudsSetting::Equalizer { .. } if old.equalizer_type == EqualizerType::Undetected => Ok(()),
+        BudsSetting::Equalizer { .. } => buds_info.send(new_equalizer(old.equalizer_type)).await,
+
+        BudsSetting::TouchpadAction { .. }
+            if old.touchpad_option_left == TouchpadOption::Undetected
+                || old.touchpad_option_right == TouchpadOption::Undetected =>
+        {
+            Ok(())
+        }
+        BudsSetting::TouchpadAction { .. } => {
+            let msg = set_touchpad_option::new(old.touchpad_option_left, old.touchpad_option_right);
+            buds_info.send(msg).await
+        }
+
+        BudsSetting::AmbientVolume { .. } => {
+            if buds_info.has_feature(Feature::ExtraHighAmbientVolume) {
+                buds_info
+                    .send(ambient_mode::SetExtraHighVolume::new(
+                        old.extra_high_ambient_volume,
+                    ))
+                    .await?;
+            }
+            if old.ambient_sound_volume > 0 {
+                buds_info
+                    .send(ambient_mode::SetAmbientVolume::new(
+                        old.ambient_sound_volume,
+                    ))
+                    .await?;
+            }
+            buds_info
+                .send(ambient_mode::SetAmbientMode::new(old.ambient_sound_enabled))
+                .await
+        }
     }
 }
 
 // Set the actual value
 async fn set_buds_option(
-    key: &str,
-    value: &str,
+    setting: &BudsSetting,
     buds_info: &mut BudsInfo,
-    opt_param3: &Option<String>,
-) -> Result<(), String> {
-    match key {
+) -> Result<(), CommandError> {
+    match *setting {
         // Set noise reduction
-        "noise_reduction" => set_anc(value, buds_info).await,
+        BudsSetting::NoiseReduction { enabled } => set_anc(enabled, buds_info).await,
 
         // Set Touchpad lock
-        "lock_touchpad" => lock_touchpad(value, buds_info, opt_param3).await,
+        BudsSetting::LockTouchpad { locked, function } => {
+            lock_touchpad(locked, function, buds_info).await
+        }
 
         // Set EqualizerType command
-        "equalizer" => match value.parse::<u8>() {
-            Ok(val) => {
-                let eq_type = EqualizerType::decode(val);
-                let res = buds_info.send(new_equalizer(eq_type)).await;
-                if res.is_ok() {
-                    buds_info.inner.equalizer_type = eq_type;
-                }
-                res
-            }
-            Err(_) => Err("could not parse value".to_string()),
-        },
+        BudsSetting::Equalizer { equalizer } => set_equalizer(equalizer, buds_info).await,
 
-        "touchpad_action" | "ambient_volume" => match value.parse::<u8>() {
-            Ok(val) => match key {
-                "touchpad_action" => set_touchpad_action(val, buds_info, opt_param3).await,
-                "ambient_volume" => set_ambient_volume_cmd(val, buds_info).await,
-
-                _ => Err("Invalid key to set to".to_string()),
-            },
-            Err(_) => Err("could not parse value".to_string()),
-        },
+        BudsSetting::TouchpadAction { option, side } => {
+            set_touchpad_action(option, side, buds_info).await
+        }
 
-        _ => Err("Invalid key to set to".to_string()),
+        BudsSetting::AmbientVolume { volume } => set_ambient_volume_cmd(volume, buds_info).await,
     }
 }
 
 async fn lock_touchpad(
-    value: &str,
+    locked: bool,
+    function: Option<TouchpadFunction>,
     buds_info: &mut BudsInfo,
-    p3: &Option<String>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     if buds_info.has_feature(Feature::ExtTouchpadLock) {
-        return lock_touchpad_ext(value, buds_info, p3).await;
+        return lock_touchpad_ext(locked, function, buds_info).await;
     }
 
-    let value = utils::str_to_bool(&value);
-    let msg = lock_touchpad::new(value);
+    let msg = lock_touchpad::new(locked);
     buds_info.send(msg).await?;
-    buds_info.inner.touchpads_blocked = value;
+    buds_info.inner.touchpads_blocked = locked;
     Ok(())
 }
 
 async fn lock_touchpad_ext(
-    value: &str,
+    locked: bool,
+    function: Option<TouchpadFunction>,
     buds_info: &mut BudsInfo,
-    p3: &Option<String>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     let mut msg = ExtLockTouchpad::from_ext_tap_lock_status(buds_info.inner.tab_lock_status);
 
-    // 'value' is true if lock but we want to know when to enable them here
-    let val = !utils::str_to_bool(&value);
-
-    if let Some(p3) = p3 {
-        match p3.to_lowercase().as_str() {
-            "tap" => msg.tap_on = val,
-            "doubletap" | "dtap" => msg.double_tap = val,
-            "tripletap" | "ttap" => msg.tripple_tap = val,
-            "hold" => msg.touch_and_hold = val,
-            _ => {
-                println!("Unsupported touchpad function {p3:?}. Choose from {{tap, doubletap, tripletap, hold}}");
-                return Ok(());
-            }
+    // 'locked' is true if lock but we want to know when to enable them here
+    let val = !locked;
+
+    if let Some(function) = function {
+        match function {
+            TouchpadFunction::Tap => msg.tap_on = val,
+            TouchpadFunction::DoubleTap => msg.double_tap = val,
+            TouchpadFunction::TripleTap => msg.tripple_tap = val,
+            TouchpadFunction::Hold => msg.touch_and_hold = val,
         };
     } else {
         msg.tap_on = val;
@@ -144,50 +238,61 @@ async fn lock_touchpad_ext(
 }
 
 /// Set the anc status
-async fn set_anc(value: &str, buds_info: &mut BudsInfo) -> Result<(), String> {
+async fn set_anc(enabled: bool, buds_info: &mut BudsInfo) -> Result<(), CommandError> {
     check_feature(buds_info, Feature::Anc)?;
 
-    let value = utils::str_to_bool(&value);
-    buds_info.send(set_noise_reduction::new(value)).await?;
-    buds_info.inner.noise_reduction = value;
+    buds_info.send(set_noise_reduction::new(enabled)).await?;
+    buds_info.inner.noise_reduction = enabled;
+    Ok(())
+}
+
+/// Set the equalizer type
+async fn set_equalizer(
+    eq_type: EqualizerType,
+    buds_info: &mut BudsInfo,
+) -> Result<(), CommandError> {
+    if eq_type == EqualizerType::Undetected {
+        return Err(CommandError::new(
+            ErrorCode::InvalidValue,
+            "Invalid equalizer",
+        ));
+    }
+
+    buds_info.send(new_equalizer(eq_type)).await?;
+    buds_info.inner.equalizer_type = eq_type;
     Ok(())
 }
 
 /// Set the touchpad action
 async fn set_touchpad_action(
-    val: u8,
+    option: TouchpadOption,
+    side: Option<TouchpadSide>,
     buds_info: &mut BudsInfo,
-    opt_param3: &Option<String>,
-) -> Result<(), String> {
-    let option = TouchpadOption::decode(val);
+) -> Result<(), CommandError> {
     let mut left = buds_info.inner.touchpad_option_left;
     let mut right = buds_info.inner.touchpad_option_right;
 
-    if opt_param3.is_some() {
-        let side = utils::str_to_side(opt_param3.as_ref().unwrap());
-        if side.is_none() {
-            return Err("Invalid side".to_string());
-        }
-        match side.unwrap() {
-            Side::Left => left = option,
-            Side::Right => right = option,
+    match side.map(Side::from) {
+        Some(Side::Left) => left = option,
+        Some(Side::Right) => right = option,
+        None => {
+            left = option;
+            right = option;
         }
-    } else {
-        left = option;
-        right = option;
     }
 
     let msg = set_touchpad_option::new(left, right);
-    let res = buds_info.send(msg).await;
-    if res.is_ok() {
-        buds_info.inner.touchpad_option_left = left;
-        buds_info.inner.touchpad_option_right = right;
-    }
-    res
+    buds_info.send(msg).await?;
+    buds_info.inner.touchpad_option_left = left;
+    buds_info.inner.touchpad_option_right = right;
+    Ok(())
 }
 
 /// Sets the extra high ambient volume value.
-async fn set_extra_high_volume(enabled: bool, buds_info: &mut BudsInfo) -> Result<(), String> {
+async fn set_extra_high_volume(
+    enabled: bool,
+    buds_info: &mut BudsInfo,
+) -> Result<(), CommandError> {
     println!("setting extra high volume {}", enabled);
 
     buds_info
@@ -199,7 +304,7 @@ async fn set_extra_high_volume(enabled: bool, buds_info: &mut BudsInfo) -> Resul
 }
 
 /// Sets the ambient volume.
-async fn set_ambient_volume(volume: u8, buds_info: &mut BudsInfo) -> Result<(), String> {
+async fn set_ambient_volume(volume: u8, buds_info: &mut BudsInfo) -> Result<(), CommandError> {
     println!("setting ambient volume to {}", volume);
 
     buds_info
@@ -211,7 +316,7 @@ async fn set_ambient_volume(volume: u8, buds_info: &mut BudsInfo) -> Result<(),
 }
 
 /// Sets the ambient mode.
-async fn set_ambient_mode(enabled: bool, buds_info: &mut BudsInfo) -> Result<(), String> {
+async fn set_ambient_mode(enabled: bool, buds_info: &mut BudsInfo) -> Result<(), CommandError> {
     println!("setting ambient state to {}", enabled);
 
     buds_info
@@ -223,11 +328,14 @@ async fn set_ambient_mode(enabled: bool, buds_info: &mut BudsInfo) -> Result<(),
 }
 
 /// Set the ambient volume level
-async fn set_ambient_volume_cmd(val: u8, buds_info: &mut BudsInfo) -> Result<(), String> {
+async fn set_ambient_volume_cmd(val: u8, buds_info: &mut BudsInfo) -> Result<(), CommandError> {
     check_feature(buds_info, Feature::AmbientSound)?;
 
     if val > buds_info.get_max_ambientsound_volume_level() {
-        return Err("Invalid volume level".to_string());
+        return Err(CommandError::new(
+            ErrorCode::InvalidValue,
+            "Invalid volume level",
+        ));
     }
 
     // Enable/disable extra high ambient volume if needed or not.
@@ -260,47 +368,100 @@ async fn set_ambient_volume_cmd(val: u8, buds_info: &mut BudsInfo) -> Result<(),
 }
 
 /// Checks a given feature and returns an error if the feature is unsupported.
-fn check_feature(buds_info: &BudsInfo, feature: Feature) -> Result<(), String> {
+fn check_feature(buds_info: &BudsInfo, feature: Feature) -> Result<(), CommandError> {
     if !buds_info.inner.model.has_feature(feature) {
-        Err("Feature not supported by your model".to_string())
+        Err(CommandError::new(
+            ErrorCode::UnsupportedFeature,
+            "Feature not supported by your model",
+        ))
     } else {
         Ok(())
     }
 }
 
 // Toggle a given value
-pub async fn toggle(payload: &Request, device_data: &mut BudsInfo) -> String {
-    // Check required fields set
-    if payload.opt_param1.is_none() {
-        return get_err("Missing parameter");
+pub async fn toggle(
+    key: ToggleKey,
+    function: Option<TouchpadFunction>,
+    device_data: &mut BudsInfo,
+) -> String {
+    let setting = match key {
+        ToggleKey::NoiseReduction => BudsSetting::NoiseReduction {
+            enabled: !device_data.inner.noise_reduction,
+        },
+        ToggleKey::LockTouchpad => BudsSetting::LockTouchpad {
+            locked: !device_data.inner.touchpads_blocked,
+            function,
+        },
+    };
+
+    set(&setting, device_data).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use async_std::{os::unix::net::UnixStream, task};
+    use galaxy_buds_rs::model::Model;
+
+    fn statuses(results: &[SettingResult]) -> Vec<SettingStatus> {
+        results.iter().map(|i| i.status).collect()
     }
 
-    let key = payload.opt_param1.clone().unwrap();
-    let value = {
-        match key.as_str() {
-            "noise_reduction" => (!device_data.inner.noise_reduction).to_string(),
-            "lock_touchpad" => (!device_data.inner.touchpads_blocked).to_string(),
-            _ => {
-                return get_err("Invalid key");
-            }
-        }
-    };
+    #[test]
+    fn apply_all_settings() {
+        let (stream, _buds) = UnixStream::pair().unwrap();
+        let mut info = BudsInfo::new(stream, "00:00:00:00:00:01", Model::BudsPro);
 
-    // Run desired command
-    let res = set_buds_option(
-        key.as_str(),
-        value.as_str(),
-        device_data,
-        &payload.opt_param3,
-    )
-    .await;
+        let settings = [
+            BudsSetting::NoiseReduction { enabled: true },
+            BudsSetting::Equalizer {
+                equalizer: EqualizerType::BassBoost,
+            },
+        ];
+        let (results, error) = task::block_on(apply_settings(&settings, &mut info));
 
-    // Return success or error based on the success of the set command
-    if res.is_ok() {
-        let a: Response<BudsInfoInner> =
-            Response::new_success(device_data.inner.address.clone(), None);
-        serde_json::to_string(&a).unwrap()
-    } else {
-        get_err(res.err().unwrap().as_str())
+        assert!(error.is_none());
+        assert_eq!(statuses(&results), [SettingStatus::Applied; 2]);
+        assert!(info.inner.noise_reduction);
+        assert_eq!(info.inner.equalizer_type, EqualizerType::BassBoost);
+    }
+
+    #[test]
+    fn roll_back_on_failure() {
+        let (stream, _buds) = UnixStream::pair().unwrap();
+        let mut info = BudsInfo::new(stream, "00:00:00:00:00:01", Model::BudsPro);
+        info.inner.equalizer_type = EqualizerType::Soft;
+
+        let settings = [
+            BudsSetting::NoiseReduction { enabled: true },
+            BudsSetting::Equalizer {
+                equalizer: EqualizerType::Undetected,
+            },
+            BudsSetting::LockTouchpad {
+                locked: true,
+                function: None,
+            },
+        ];
+        let (results, error) = task::block_on(apply_settings(&settings, &mut info));
+
+        let error = error.unwrap();
+        assert_eq!(error.code, ErrorCode::InvalidValue);
+        assert!(error.message.contains("setting 2"), "{}", error.message);
+        assert_eq!(
+            statuses(&results),
+            [
+                SettingStatus::Applied,
+                SettingStatus::Failed,
+                SettingStatus::Skipped
+            ]
+        );
+        assert!(results[1].error.is_some());
+
+        // The applied setting got rolled back
+        assert!(!info.inner.noise_reduction);
+        assert_eq!(info.inner.equalizer_type, EqualizerType::Soft);
+        assert!(!info.inner.touchpads_blocked);
     }
 }
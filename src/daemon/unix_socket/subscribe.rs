@@ -0,0 +1,62 @@
+use super::super::bluetooth::rfcomm_connector::ConnectionData;
+use super::super::buds_config::Config;
+use super::super::buds_info::BudsInfoInner;
+use super::request_handler::{get_err, resolve_device};
+use super::{ErrorCode, Request, Response};
+
+use async_std::{
+    io::{prelude::*, BufWriter},
+    os::unix::net::UnixStream,
+    sync::{Arc, Mutex},
+};
+
+/// Keep the connection open and stream all buds events as newline delimited json
+/// to the client. If a device was requested, only its events get sent
+pub async fn handle(
+    payload: &Request,
+    write_stream: &mut BufWriter<&UnixStream>,
+    cd: Arc<Mutex<ConnectionData>>,
+    config: &Arc<Mutex<Config>>,
+) {
+    let receiver = {
+        let mut connection_data = cd.lock().await;
+        let device = resolve_device(payload, &connection_data, config).await;
+        device.map(|device| (device, connection_data.subscribers.subscribe()))
+    };
+
+    let (device, receiver) = match receiver {
+        Ok(receiver) => receiver,
+        Err(err) => {
+            send_line(get_err(ErrorCode::DeviceNotFound, &err), write_stream).await;
+            return;
+        }
+    };
+
+    // Confirm the subscription
+    let response: Response<BudsInfoInner> = Response::new_success(&device, None);
+    if !send_line(serde_json::to_string(&response).unwrap(), write_stream).await {
+        return;
+    }
+
+    while let Ok(event) = receiver.recv().await {
+        if !device.is_empty() && !event.address().is_empty() && event.address() != device {
+            continue;
+        }
+
+        // Stop streaming as soon as the client went away
+        if !send_line(serde_json::to_string(&event).unwrap(), write_stream).await {
+            return;
+        }
+    }
+}
+
+// Write a single line to the client. Return true on success
+async fn send_line(mut line: String, write_stream: &mut BufWriter<&UnixStream>) -> bool {
+    line.push('\n');
+
+    if write_stream.write_all(line.as_bytes()).await.is_err() {
+        return false;
+    }
+
+    write_stream.flush().await.is_ok()
+}
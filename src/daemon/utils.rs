@@ -1,4 +1,7 @@
-use galaxy_buds_rs::message::bud_property::Side;
+use galaxy_buds_rs::{
+    message::bud_property::{EqualizerType, Side, TouchpadOption},
+    model::Model,
+};
 
 /// Converts a str to a boolean. All undefineable
 /// values are false
@@ -34,3 +37,78 @@ pub fn str_to_side<S: AsRef<str>>(s: S) -> Option<Side> {
         _ => return None,
     })
 }
+
+// parse tap action strings to enum variants
+pub fn str_to_tap_action<S: AsRef<str>>(s: S) -> TouchpadOption {
+    match s.as_ref().to_lowercase().as_str() {
+        "volume" => TouchpadOption::Volume,
+        "spotify" => TouchpadOption::Spotify,
+        "voice-command" => TouchpadOption::VoiceCommand,
+        "anc" => TouchpadOption::NoiseCanceling,
+        "disconnect" => TouchpadOption::Disconnect,
+        _ => TouchpadOption::Undetected,
+    }
+}
+
+// parse equalizer strings to enum variants
+pub fn str_to_equalizer<S: AsRef<str>>(s: S) -> EqualizerType {
+    match s.as_ref().to_lowercase().as_str() {
+        "normal" | "off" => EqualizerType::Normal,
+        "bass" | "bb" => EqualizerType::BassBoost,
+        "soft" => EqualizerType::Soft,
+        "dynamic" | "dyn" => EqualizerType::Dynamic,
+        "clear" => EqualizerType::Clear,
+        "treble" => EqualizerType::TrebleBoost,
+        _ => EqualizerType::Undetected,
+    }
+}
+
+// Get the name of a tap action, as accepted by str_to_tap_action
+pub fn tap_action_to_str(option: TouchpadOption) -> Option<&'static str> {
+    Some(match option {
+        TouchpadOption::Volume => "volume",
+        TouchpadOption::Spotify => "spotify",
+        TouchpadOption::VoiceCommand => "voice-command",
+        TouchpadOption::NoiseCanceling => "anc",
+        TouchpadOption::Disconnect => "disconnect",
+        _ => return None,
+    })
+}
+
+// Get the name of an equalizer type, as accepted by str_to_equalizer
+pub fn equalizer_to_str(equalizer: EqualizerType) -> Option<&'static str> {
+    Some(match equalizer {
+        EqualizerType::Normal => "normal",
+        EqualizerType::BassBoost => "bass",
+        EqualizerType::Soft => "soft",
+        EqualizerType::Dynamic => "dynamic",
+        EqualizerType::Clear => "clear",
+        EqualizerType::TrebleBoost => "treble",
+        _ => return None,
+    })
+}
+
+/// Get the product name of a model
+pub fn model_name(model: Model) -> &'static str {
+    match model {
+        Model::Buds => "Galaxy Buds",
+        Model::BudsPlus => "Galaxy Buds+",
+        Model::BudsLive => "Galaxy Buds Live",
+        Model::BudsPro => "Galaxy Buds Pro",
+        Model::Buds2 => "Galaxy Buds2",
+        Model::BudsPro2 => "Galaxy Buds2 Pro",
+    }
+}
+
+/// Parse the name of a model as used in config and script files
+pub fn parse_model(s: &str) -> Result<Model, String> {
+    Ok(match s.to_lowercase().replace('_', "-").as_str() {
+        "buds" => Model::Buds,
+        "buds-plus" | "buds+" => Model::BudsPlus,
+        "buds-live" => Model::BudsLive,
+        "buds-pro" => Model::BudsPro,
+        "buds2" | "buds-2" => Model::Buds2,
+        "buds2-pro" | "buds-2-pro" => Model::BudsPro2,
+        _ => return Err(format!("Unknown model: {}", s)),
+    })
+}
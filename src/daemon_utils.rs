@@ -1,7 +1,7 @@
 use std::{
-    env,
+    env, fs,
     path::Path,
-    process::{exit, Command, Stdio},
+    process::{self, exit, Command, Stdio},
 };
 
 use nix::{
@@ -9,6 +9,37 @@ use nix::{
     unistd::Pid,
 };
 
+const DAEMON_PATH: &str = "/tmp/earbuds.sock";
+
+/// Get the path of the daemons socket. Can be set
+/// using EARBUDS_SOCKET to talk to another daemon
+pub fn socket_path() -> String {
+    env::var("EARBUDS_SOCKET")
+        .ok()
+        .filter(|i| !i.is_empty())
+        .unwrap_or_else(|| DAEMON_PATH.to_owned())
+}
+
+/// Keep simulated buds away from the config and battery history of the user by using
+/// a new temporary directory for them. The simulation also gets its own daemon socket,
+/// unless one is set using EARBUDS_SOCKET. Returns the path of the socket
+pub fn isolate_simulation() -> Result<String, String> {
+    let dir = env::temp_dir().join(format!("earbuds-simulation-{}", process::id()));
+    fs::create_dir_all(&dir).map_err(|e| format!("Can't create {}: {}", dir.display(), e))?;
+
+    env::set_var("XDG_CONFIG_HOME", dir.join("config"));
+    env::set_var("XDG_DATA_HOME", dir.join("data"));
+
+    let socket = env::var("EARBUDS_SOCKET")
+        .ok()
+        .filter(|i| !i.is_empty())
+        .unwrap_or_else(|| dir.join("earbuds.sock").to_string_lossy().into_owned());
+
+    println!("Simulating buds in {}", dir.display());
+    println!("Use them with EARBUDS_SOCKET={} earbuds", socket);
+    Ok(socket)
+}
+
 /// Start the daemon detached from the current cli
 pub fn start() -> bool {
     let curr_exe = env::current_exe().expect("Couldn't get current executable!");
@@ -10,12 +10,11 @@ use clap_complete::{
     Generator,
 };
 use cmd::socket_client::SocketClient;
+use daemon::DaemonOptions;
 use human_panic::setup_panic;
 
 use std::process::exit;
 
-const DAEMON_PATH: &str = "/tmp/earbuds.sock";
-
 #[async_std::main]
 async fn main() {
     setup_panic!();
@@ -27,40 +26,57 @@ async fn main() {
 
     let clap = { cli::build().get_matches() };
 
+    // Simulated and replayed buds get their own config and daemon
+    let simulated = clap.contains_id("fake-buds") || clap.subcommand_matches("replay").is_some();
+    let daemon_path = if simulated {
+        match daemon_utils::isolate_simulation() {
+            Ok(path) => path,
+            Err(err) => {
+                eprintln!("{}", err);
+                exit(1);
+            }
+        }
+    } else {
+        daemon_utils::socket_path()
+    };
+
     // Kill daemon if desired and running
-    if clap.contains_id("kill-daemon")
-        && daemon_utils::check_running(DAEMON_PATH.to_owned()).is_err()
-    {
-        if !daemon_utils::kill(clap.contains_id("kill-daemon"), DAEMON_PATH) {
+    if clap.get_flag("kill-daemon") && daemon_utils::check_running(&daemon_path).is_err() {
+        if !daemon_utils::kill(clap.get_flag("kill-daemon"), &daemon_path) {
             println!("Couldn't kill daemon");
             return;
         }
     }
 
     // Run daemon on -k
-    if clap.contains_id("daemon") {
+    if clap.get_flag("daemon") {
         // Check if a daemon is already running
-        if let Err(err) = daemon_utils::check_running(DAEMON_PATH) {
+        if let Err(err) = daemon_utils::check_running(&daemon_path) {
             // Don't print error output if -q is passed
-            if !clap.contains_id("quiet") {
+            if !clap.get_flag("quiet") {
                 eprintln!("{}", err);
             }
             exit(1);
         }
         // Block if --no-fork is provided
-        if clap.contains_id("no-fork") {
-            daemon::run_daemon(DAEMON_PATH.to_owned()).await;
+        if clap.get_flag("no-fork") {
+            let options = DaemonOptions {
+                fake_buds: clap.get_one::<String>("fake-buds").cloned(),
+                record: clap.get_one::<String>("record").cloned(),
+                ..DaemonOptions::default()
+            };
+            daemon::run_daemon(daemon_path, options).await;
             return;
         } else
         // Start daemon detached
-        if daemon_utils::start() && !clap.contains_id("quiet") {
+        if daemon_utils::start() && !clap.get_flag("quiet") {
             println!("Daemon started successfully")
         }
         return;
     }
     // Late return to allow a
     // combination of -k and -d
-    if clap.contains_id("kill-daemon") {
+    if clap.get_flag("kill-daemon") {
         return;
     }
 
@@ -70,24 +86,47 @@ async fn main() {
         return;
     }
 
+    // The battery history is read from disk and doesn't need a daemon
+    if let Some(battery) = clap.subcommand_matches("battery") {
+        if let Some(history) = battery.subcommand_matches("history") {
+            cmd::battery::history(history);
+        }
+        return;
+    }
+
+    // Replay a capture in a foreground daemon
+    if let Some(subcommand) = clap.subcommand_matches("replay") {
+        if let Err(err) = daemon_utils::check_running(&daemon_path) {
+            eprintln!("{}", err);
+            exit(1);
+        }
+
+        let options = DaemonOptions {
+            replay: subcommand.get_one::<String>("file").cloned(),
+            ..DaemonOptions::default()
+        };
+        daemon::run_daemon(daemon_path, options).await;
+        return;
+    }
+
     // From here we need a running daemon, so ensure one is running
-    if daemon_utils::check_running(DAEMON_PATH.to_owned()).is_ok() {
+    if daemon_utils::check_running(&daemon_path).is_ok() {
         if !daemon_utils::start() {
             exit(1);
         } else {
-            if !clap.contains_id("quiet") {
+            if !clap.get_flag("quiet") {
                 println!("Daemon started successfully")
             }
             // TODO wait for deamon to be ready
             std::thread::sleep(std::time::Duration::from_millis(1000));
         }
     }
-    run_subcommands(clap);
+    run_subcommands(clap, &daemon_path);
 }
 
-fn run_subcommands(clap: ArgMatches) {
+fn run_subcommands(clap: ArgMatches, daemon_path: &str) {
     // Create a new daemon connection client
-    let mut socket_client = match SocketClient::new(&DAEMON_PATH) {
+    let mut socket_client = match SocketClient::new(daemon_path) {
         Ok(v) => v,
         Err(err) => {
             eprintln!("Could not connect to daemon: {:?}", err);
@@ -100,40 +139,78 @@ fn run_subcommands(clap: ArgMatches) {
         cmd::info::show(&mut socket_client, subcommand);
     }
 
+    // Run watch command
+    if let Some(subcommand) = clap.subcommand_matches("watch") {
+        cmd::watch::watch(&mut socket_client, subcommand);
+    }
+
     // Run set command
     if let Some(subcommand) = clap.subcommand_matches("set") {
         cmd::set_value::set(
             &mut socket_client,
             subcommand,
-            false,
             subcommand.get_one::<String>("value").expect("required"),
         );
     }
 
     // Run disable command
     if let Some(subcommand) = clap.subcommand_matches("disable") {
-        cmd::set_value::set(&mut socket_client, subcommand, false, "off");
+        cmd::set_value::set(&mut socket_client, subcommand, "off");
     }
 
     // Run enable command
     if let Some(subcommand) = clap.subcommand_matches("enable") {
-        cmd::set_value::set(&mut socket_client, subcommand, false, "on");
+        cmd::set_value::set(&mut socket_client, subcommand, "on");
     }
 
     // Run toggle command
     if let Some(subcommand) = clap.subcommand_matches("toggle") {
-        cmd::set_value::set(
-            &mut socket_client,
-            subcommand,
-            true,
-            subcommand.get_one::<String>("value").expect("required"),
-        );
+        cmd::set_value::toggle(&mut socket_client, subcommand);
+    }
+
+    // Run apply command
+    if let Some(subcommand) = clap.subcommand_matches("apply") {
+        cmd::apply::apply(&mut socket_client, subcommand);
+    }
+
+    // Run profile commands
+    if let Some(profile) = clap.subcommand_matches("profile") {
+        if let Some(list) = profile.subcommand_matches("list") {
+            cmd::profile::list(&mut socket_client, list);
+        }
+        if let Some(save) = profile.subcommand_matches("save") {
+            cmd::profile::save(&mut socket_client, save);
+        }
+        if let Some(load) = profile.subcommand_matches("load") {
+            cmd::profile::load(&mut socket_client, load);
+        }
+        if let Some(delete) = profile.subcommand_matches("delete") {
+            cmd::profile::delete(&mut socket_client, delete);
+        }
+    }
+
+    // Run devices commands
+    if let Some(devices) = clap.subcommand_matches("devices") {
+        if let Some(set_default) = devices.subcommand_matches("set-default") {
+            cmd::devices::set_default(&mut socket_client, set_default);
+        } else {
+            cmd::devices::list(&mut socket_client, devices);
+        }
     }
 
     // Run toggle command
     if let Some(config) = clap.subcommand_matches("config") {
         if let Some(set) = config.subcommand_matches("set") {
-            cmd::config_set::set(&mut socket_client, set);
+            cmd::config::set(&mut socket_client, set);
+        }
+        if let Some(get) = config.subcommand_matches("get") {
+            cmd::config::get(&mut socket_client, get);
+        }
+        if let Some(unset) = config.subcommand_matches("unset") {
+            cmd::config::unset(&mut socket_client, unset);
+        }
+        if let Some(list) = config.subcommand_matches("list") {
+            cmd::config::list(&mut socket_client, list);
         }
     }
 
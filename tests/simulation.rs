@@ -0,0 +1,288 @@
+/*
+ * End to end tests running the daemon with simulated buds and driving it using the cli
+ */
+
+use std::{
+    env, fs,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::{Child, Command, Output, Stdio},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+const ADDRESS: &str = "00:00:00:00:00:01";
+
+// Buds which get ready right away, report a touchpad
+// action and change their battery levels later on
+const SCRIPT: &str = r#"
+address = "00:00:00:00:00:01"
+model = "buds-live"
+
+# Batteries at 80%, 81% and 50% (case), both buds in the open case
+[[frames]]
+id = "extended_status_updated"
+payload = "0a 00 50 51 01 00 33 32 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00"
+
+# Double tap on the left bud
+[[frames]]
+delay_ms = 100
+id = "touchpad_action"
+payload = "00 02"
+
+# The left bud lost a percent while being worn
+[[frames]]
+delay_ms = 2000
+id = "status_updated"
+payload = "01 4f 51 01 00 11 32 00 00 00"
+"#;
+
+// Buds which never send their status
+const SILENT_SCRIPT: &str = r#"
+address = "00:00:00:00:00:01"
+model = "buds-live"
+"#;
+
+const CAPTURE: &str = r#"{"timestamp":0,"address":"00:00:00:00:00:01","model":"BudsLive","direction":"in","data":""}
+"#;
+
+/// A daemon simulating buds, using its own home directory
+struct Daemon {
+    child: Child,
+    dir: PathBuf,
+}
+
+impl Daemon {
+    // Start a daemon simulating the buds of the script
+    fn start(name: &str) -> Self {
+        Self::spawn(
+            name,
+            "script.toml",
+            SCRIPT,
+            &["-d", "--no-fork", "--fake-buds"],
+        )
+    }
+
+    // Start a daemon simulating buds which never get ready
+    fn start_silent(name: &str) -> Self {
+        Self::spawn(
+            name,
+            "script.toml",
+            SILENT_SCRIPT,
+            &["-d", "--no-fork", "--fake-buds"],
+        )
+    }
+
+    // Start a daemon replaying the capture
+    fn replay(name: &str) -> Self {
+        Self::spawn(name, "capture.jsonl", CAPTURE, &["replay"])
+    }
+
+    // Write the file and start a daemon using it
+    fn spawn(name: &str, file: &str, content: &str, args: &[&str]) -> Self {
+        let dir = env::temp_dir().join(format!("earbuds-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("home")).unwrap();
+
+        let file = dir.join(file);
+        fs::write(&file, content).unwrap();
+
+        let child = earbuds(&dir)
+            .args(args)
+            .arg(&file)
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        let mut daemon = Daemon { child, dir };
+
+        // Wait for the daemon to remember the simulated buds. Running the
+        // cli before the socket exists would start another daemon
+        let start = Instant::now();
+        while !daemon.dir.join("earbuds.sock").exists()
+            || !daemon.run(&["devices"]).contains(ADDRESS)
+        {
+            if start.elapsed() > Duration::from_secs(10) {
+                panic!("Daemon didn't start");
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        daemon
+    }
+
+    // Run the cli and return its output
+    fn run(&mut self, args: &[&str]) -> String {
+        if let Ok(Some(status)) = self.child.try_wait() {
+            panic!("Daemon exited with {}", status);
+        }
+
+        let Output { stdout, .. } = earbuds(&self.dir).args(args).output().unwrap();
+        String::from_utf8(stdout).unwrap()
+    }
+
+    fn run_json(&mut self, args: &[&str]) -> serde_json::Value {
+        let mut args = args.to_vec();
+        args.extend_from_slice(&["-o", "json"]);
+        serde_json::from_str(&self.run(&args)).unwrap()
+    }
+
+    // Run 'watch' until it printed a status matching 'f'
+    fn watch_until(&self, f: impl Fn(&serde_json::Value) -> bool) -> bool {
+        let mut watch = earbuds(&self.dir)
+            .args(["watch", "-o", "json"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let stdout = watch.stdout.take().unwrap();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let start = Instant::now();
+        let mut found = false;
+        while let Some(timeout) = Duration::from_secs(10).checked_sub(start.elapsed()) {
+            match rx.recv_timeout(timeout) {
+                Ok(line) if f(&serde_json::from_str(&line).unwrap()) => {
+                    found = true;
+                    break;
+                }
+                Ok(_) => (),
+                Err(_) => break,
+            }
+        }
+
+        let _ = watch.kill();
+        let _ = watch.wait();
+        found
+    }
+
+    // The config of the simulation, kept in its own temporary directory
+    fn simulation_config(&self) -> Option<String> {
+        let dir = self
+            .dir
+            .join(format!("earbuds-simulation-{}", self.child.id()));
+        fs::read_to_string(dir.join("config/livebuds/config.toml")).ok()
+    }
+
+    fn user_config(&self) -> Option<String> {
+        let path = self.dir.join("home/.config/livebuds/config.toml");
+        fs::read_to_string(path).ok()
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+// The cli, isolated from the users home directory and daemon
+fn earbuds(dir: &Path) -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_earbuds"));
+    cmd.env("HOME", dir.join("home"))
+        .env("TMPDIR", dir)
+        .env("EARBUDS_SOCKET", dir.join("earbuds.sock"))
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("XDG_DATA_HOME")
+        // The connection handler blocks one executor thread while waiting for devices
+        .env("ASYNC_STD_THREAD_COUNT", "4");
+    cmd
+}
+
+#[test]
+fn lists_simulated_buds() {
+    let mut daemon = Daemon::start("devices");
+
+    let response = daemon.run_json(&["devices"]);
+    assert_eq!(response["status"], "success");
+    assert_eq!(response["payload"][0]["address"], ADDRESS);
+
+    // The buds only get stored in the config of the simulation
+    let config = daemon.simulation_config().unwrap_or_default();
+    assert!(config.contains(ADDRESS), "{}", config);
+    assert!(daemon.user_config().is_none());
+}
+
+#[test]
+fn status_needs_ready_buds() {
+    let mut daemon = Daemon::start_silent("status");
+
+    // The simulated buds never send their status
+    let response = daemon.run_json(&["status"]);
+    assert_eq!(response["status"], "error");
+    assert_eq!(response["error_code"], "no_device");
+}
+
+#[test]
+fn status_of_ready_buds() {
+    let mut daemon = Daemon::start("ready");
+
+    // The battery level of the left bud changes later on
+    let response = daemon.run_json(&["status"]);
+    assert_eq!(response["status"], "success");
+    assert_eq!(response["payload"]["ready"], true);
+    assert_eq!(response["payload"]["batt_right"], 81);
+    assert_eq!(response["payload"]["batt_case"], 50);
+
+    let response = daemon.run_json(&["devices"]);
+    assert_eq!(response["payload"][0]["ready"], true);
+}
+
+#[test]
+fn watch_shows_changes() {
+    let daemon = Daemon::start("watch");
+
+    // The touchpad action doesn't break the connection and
+    // the later status update gets shown
+    assert!(daemon.watch_until(|status| status["payload"]["batt_left"] == 79));
+}
+
+#[test]
+fn simulation_keeps_user_config() {
+    let mut daemon = Daemon::start("config");
+
+    let response = daemon.run_json(&["devices", "set-default", ADDRESS]);
+    assert_eq!(response["status"], "success");
+
+    let response = daemon.run_json(&["devices"]);
+    assert_eq!(response["payload"][0]["default"], true);
+
+    let user_config = daemon.user_config().unwrap_or_default();
+    assert!(!user_config.contains(ADDRESS), "{}", user_config);
+}
+
+#[test]
+fn replay_keeps_user_config() {
+    let mut daemon = Daemon::replay("replay");
+
+    let response = daemon.run_json(&["devices"]);
+    assert_eq!(response["payload"][0]["address"], ADDRESS);
+
+    let config = daemon.simulation_config().unwrap_or_default();
+    assert!(config.contains(ADDRESS), "{}", config);
+    assert!(daemon.user_config().is_none());
+}
+
+#[test]
+fn battery_history_without_daemon() {
+    let dir = env::temp_dir().join(format!("earbuds-test-history-{}", std::process::id()));
+    fs::create_dir_all(dir.join("home")).unwrap();
+
+    let output = earbuds(&dir).args(["battery", "history"]).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let daemon_started = dir.join("earbuds.sock").exists();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(output.status.success());
+    assert!(stdout.contains("No battery history recorded"), "{}", stdout);
+    assert!(!daemon_started);
+}